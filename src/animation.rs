@@ -0,0 +1,440 @@
+//! Loading body animations into frames with their own durations, from separate
+//! image files, a sprite sheet with a JSON frame map, or an animated GIF/APNG/WebP.
+
+use std::{
+	fs::File,
+	io::BufReader,
+	path::{Path, PathBuf},
+};
+
+use image::{
+	codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+	AnimationDecoder, RgbaImage,
+};
+use serde::{Deserialize, Deserializer};
+
+/// GIFs with shorter frames than this are played at [`SHORT_DELAY_FALLBACK`]
+/// instead, like browsers do.
+const SHORT_DELAY: f64 = 0.02;
+const SHORT_DELAY_FALLBACK: f64 = 0.1;
+
+/// Where a body animation comes from.
+#[derive(Debug, Clone)]
+pub enum Source {
+	/// One image per frame, all shown for the same time.
+	Files { paths: Vec<String>, fps: f64 },
+	/// A sprite sheet described by an Aseprite (or TexturePacker) JSON export,
+	/// optionally only the frames of one Aseprite tag.
+	SpriteSheet { path: String, tag: Option<String> },
+	/// An animated GIF, APNG or WebP, with the delays stored in the file.
+	Animated(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+	pub image: RgbaImage,
+	/// Seconds the frame is shown for.
+	pub duration: f64,
+}
+
+/// Index of the frame showing `t` seconds in, looping over `durations`.
+pub fn frame_at(durations: &[f64], t: f64) -> usize {
+	let total: f64 = durations.iter().sum();
+	if durations.is_empty() || total <= 0.0 {
+		return 0;
+	}
+
+	// The epsilon keeps frames of evenly timed animations from ending a hair early,
+	// including the last one, which wraps around to the first.
+	let mut t = t.rem_euclid(total) + 1e-9;
+	if t >= total {
+		t -= total;
+	}
+	let mut end = 0.0;
+	for (i, duration) in durations.iter().enumerate() {
+		end += duration;
+		if t < end {
+			return i;
+		}
+	}
+	durations.len() - 1
+}
+
+pub fn load(source: &Source) -> Result<Vec<Frame>, String> {
+	let frames = match source {
+		Source::Files { paths, fps } => paths
+			.iter()
+			.map(|path| {
+				Ok(Frame {
+					image: open(path)?,
+					duration: 1.0 / fps,
+				})
+			})
+			.collect::<Result<Vec<_>, String>>()?,
+		Source::SpriteSheet { path, tag } => load_sprite_sheet(path, tag.as_deref())?,
+		Source::Animated(path) => load_animated(path)?,
+	};
+	if frames.is_empty() {
+		return Err(format!("{source:?} has no frames"));
+	}
+	Ok(frames)
+}
+
+fn open(path: &str) -> Result<RgbaImage, String> {
+	image::open(path)
+		.map(|image| image.to_rgba8())
+		.map_err(|e| format!("failed to open {path}: {e}"))
+}
+
+fn load_animated(path: &str) -> Result<Vec<Frame>, String> {
+	let file = File::open(path).map_err(|e| format!("failed to open {path}: {e}"))?;
+	let reader = BufReader::new(file);
+	let extension = Path::new(path)
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.map(str::to_lowercase);
+
+	let frames = match extension.as_deref() {
+		Some("gif") => GifDecoder::new(reader).map(|d| d.into_frames().collect_frames()),
+		Some("png" | "apng") => PngDecoder::new(reader)
+			.and_then(|d| d.apng())
+			.map(|d| d.into_frames().collect_frames()),
+		Some("webp") => WebPDecoder::new(reader).map(|d| d.into_frames().collect_frames()),
+		_ => return Err(format!("{path} is not a GIF, APNG or WebP file")),
+	};
+	let frames = frames
+		.and_then(|frames| frames)
+		.map_err(|e| format!("failed to decode {path}: {e}"))?;
+
+	Ok(frames
+		.into_iter()
+		.map(|frame| {
+			let (numer, denom) = frame.delay().numer_denom_ms();
+			let mut duration = numer as f64 / denom as f64 / 1000.0;
+			if duration < SHORT_DELAY {
+				duration = SHORT_DELAY_FALLBACK;
+			}
+			Frame {
+				image: frame.into_buffer(),
+				duration,
+			}
+		})
+		.collect())
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct Rect {
+	x: u32,
+	y: u32,
+	w: u32,
+	h: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct Size {
+	w: u32,
+	h: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SheetFrame {
+	frame: Rect,
+	/// Stored turned 90° clockwise in the sheet.
+	#[serde(default)]
+	rotated: bool,
+	/// Where the trimmed `frame` goes in the untrimmed `source_size`.
+	sprite_source_size: Option<Rect>,
+	source_size: Option<Size>,
+	/// Milliseconds.
+	#[serde(default = "default_sheet_duration")]
+	duration: f64,
+}
+
+fn default_sheet_duration() -> f64 {
+	100.0
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+	#[default]
+	Forward,
+	Reverse,
+	PingPong,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FrameTag {
+	name: String,
+	from: usize,
+	to: usize,
+	#[serde(default)]
+	direction: Direction,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SheetMeta {
+	image: String,
+	#[serde(default)]
+	frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct SpriteSheet {
+	#[serde(deserialize_with = "frames_in_order")]
+	frames: Vec<SheetFrame>,
+	meta: SheetMeta,
+}
+
+/// Aseprite writes `frames` either as an array or as an object keyed by frame
+/// name. Objects are read in file order, which is the animation order.
+fn frames_in_order<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Vec<SheetFrame>, D::Error> {
+	struct Visitor;
+
+	impl<'de> serde::de::Visitor<'de> for Visitor {
+		type Value = Vec<SheetFrame>;
+
+		fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			f.write_str("an array or map of frames")
+		}
+
+		fn visit_seq<A: serde::de::SeqAccess<'de>>(
+			self,
+			mut seq: A,
+		) -> Result<Self::Value, A::Error> {
+			let mut frames = Vec::new();
+			while let Some(frame) = seq.next_element()? {
+				frames.push(frame);
+			}
+			Ok(frames)
+		}
+
+		fn visit_map<A: serde::de::MapAccess<'de>>(
+			self,
+			mut map: A,
+		) -> Result<Self::Value, A::Error> {
+			let mut frames = Vec::new();
+			while let Some((_, frame)) = map.next_entry::<String, SheetFrame>()? {
+				frames.push(frame);
+			}
+			Ok(frames)
+		}
+	}
+
+	deserializer.deserialize_any(Visitor)
+}
+
+fn read_sprite_sheet(path: &str) -> Result<SpriteSheet, String> {
+	let contents =
+		std::fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+	serde_json::from_str(&contents).map_err(|e| format!("failed to parse {path}: {e}"))
+}
+
+/// Indices of the frames `tag` plays, in order.
+fn tag_frames(sheet: &SpriteSheet, tag: &str) -> Result<Vec<usize>, String> {
+	let tag = sheet
+		.meta
+		.frame_tags
+		.iter()
+		.find(|t| t.name == tag)
+		.ok_or_else(|| format!("no frame tag named \"{tag}\""))?;
+	if tag.from > tag.to || tag.to >= sheet.frames.len() {
+		return Err(format!("frame tag \"{}\" is out of range", tag.name));
+	}
+
+	let forward = tag.from..=tag.to;
+	Ok(match tag.direction {
+		Direction::Forward => forward.collect(),
+		Direction::Reverse => forward.rev().collect(),
+		// There and back without showing either end twice in a row.
+		Direction::PingPong => forward
+			.clone()
+			.chain(
+				forward
+					.rev()
+					.skip(1)
+					.take((tag.to - tag.from).saturating_sub(1)),
+			)
+			.collect(),
+	})
+}
+
+fn load_sprite_sheet(path: &str, tag: Option<&str>) -> Result<Vec<Frame>, String> {
+	let sheet = read_sprite_sheet(path)?;
+	let order = match tag {
+		Some(tag) => tag_frames(&sheet, tag).map_err(|e| format!("{path}: {e}"))?,
+		None => (0..sheet.frames.len()).collect(),
+	};
+	let image_path: PathBuf = Path::new(path)
+		.parent()
+		.unwrap_or(Path::new(""))
+		.join(&sheet.meta.image);
+	let atlas = open(&image_path.to_string_lossy())?;
+
+	order
+		.into_iter()
+		.map(|i| {
+			let frame = &sheet.frames[i];
+			let Rect { x, y, w, h } = frame.frame;
+			// Rotated frames take up their size turned sideways in the sheet.
+			let (sheet_w, sheet_h) = if frame.rotated { (h, w) } else { (w, h) };
+			if x + sheet_w > atlas.width() || y + sheet_h > atlas.height() {
+				return Err(format!("{path}: frame at {x},{y} is outside the sheet"));
+			}
+
+			let mut image = image::imageops::crop_imm(&atlas, x, y, sheet_w, sheet_h).to_image();
+			if frame.rotated {
+				image = image::imageops::rotate270(&image);
+			}
+			if let (Some(offset), Some(size)) = (frame.sprite_source_size, frame.source_size) {
+				let mut untrimmed = RgbaImage::new(size.w, size.h);
+				image::imageops::overlay(&mut untrimmed, &image, offset.x as i64, offset.y as i64);
+				image = untrimmed;
+			}
+
+			Ok(Frame {
+				image,
+				duration: frame.duration / 1000.0,
+			})
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use image::Rgba;
+
+	use super::*;
+
+	fn sheet(json: &str) -> SpriteSheet {
+		serde_json::from_str(json).unwrap()
+	}
+
+	fn tagged(direction: &str, from: usize, to: usize) -> Vec<usize> {
+		let frames = ["{\"frame\":{\"x\":0,\"y\":0,\"w\":1,\"h\":1}}"; 6].join(",");
+		let sheet = sheet(&format!(
+			"{{\"frames\":[{frames}],\"meta\":{{\"image\":\"a.png\",\"frameTags\":[\
+			{{\"name\":\"t\",\"from\":{from},\"to\":{to},\"direction\":\"{direction}\"}}]}}}}"
+		));
+		tag_frames(&sheet, "t").unwrap()
+	}
+
+	#[test]
+	fn plays_tags_in_their_direction() {
+		assert_eq!(tagged("forward", 1, 4), [1, 2, 3, 4]);
+		assert_eq!(tagged("reverse", 1, 4), [4, 3, 2, 1]);
+		// Looping this shows 1 2 3 4 3 2 1 2 ..., never either end twice in a row.
+		assert_eq!(tagged("pingpong", 1, 4), [1, 2, 3, 4, 3, 2]);
+		assert_eq!(tagged("pingpong", 1, 2), [1, 2]);
+		assert_eq!(tagged("pingpong", 3, 3), [3]);
+	}
+
+	#[test]
+	fn rejects_unknown_and_out_of_range_tags() {
+		let sheet = sheet(
+			"{\"frames\":[{\"frame\":{\"x\":0,\"y\":0,\"w\":1,\"h\":1}}],\"meta\":{\"image\":\"a.png\",\
+			\"frameTags\":[{\"name\":\"t\",\"from\":0,\"to\":1}]}}",
+		);
+		assert!(tag_frames(&sheet, "t").is_err());
+		assert!(tag_frames(&sheet, "missing").is_err());
+	}
+
+	#[test]
+	fn frames_change_exactly_at_their_boundaries() {
+		let durations = [0.1, 0.2, 0.1];
+		assert_eq!(frame_at(&durations, 0.0), 0);
+		assert_eq!(frame_at(&durations, 0.1), 1);
+		assert_eq!(frame_at(&durations, 0.299), 1);
+		assert_eq!(frame_at(&durations, 0.3), 2);
+		// Wraps around to the start after the last frame, and from before it.
+		assert_eq!(frame_at(&durations, 0.4), 0);
+		assert_eq!(frame_at(&durations, 0.5), 1);
+		assert_eq!(frame_at(&durations, 4.0), 0);
+		assert_eq!(frame_at(&durations, -0.1), 2);
+		assert_eq!(frame_at(&[], 1.0), 0);
+		assert_eq!(frame_at(&[0.0, 0.0], 1.0), 0);
+	}
+
+	#[test]
+	fn evenly_timed_frames_dont_end_early() {
+		let durations = [1.0 / 30.0; 30];
+		for i in 0..90 {
+			assert_eq!(frame_at(&durations, i as f64 / 30.0), i % 30);
+		}
+	}
+
+	/// A 2×3 sprite with a different color in every pixel.
+	fn sprite() -> RgbaImage {
+		RgbaImage::from_fn(2, 3, |x, y| Rgba([x as u8 * 100, y as u8 * 50, 7, 255]))
+	}
+
+	fn load_sheet(name: &str, atlas: &RgbaImage, json: &str) -> Vec<Frame> {
+		let dir =
+			std::env::temp_dir().join(format!("funfriend-sheet-{name}-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		atlas.save(dir.join("sheet.png")).unwrap();
+		let path = dir.join("sheet.json");
+		std::fs::write(&path, json).unwrap();
+		let frames = load_sprite_sheet(&path.to_string_lossy(), None);
+		std::fs::remove_dir_all(&dir).unwrap();
+		frames.unwrap()
+	}
+
+	#[test]
+	fn unrotates_and_untrims_texture_packer_frames() {
+		// Stored turned 90° clockwise at 1,0: the sprite's top left pixel ends up
+		// in the top right corner and its bottom left one in the top left corner.
+		let sprite = sprite();
+		let mut atlas = RgbaImage::new(5, 3);
+		for (x, y, pixel) in sprite.enumerate_pixels() {
+			atlas.put_pixel(1 + (sprite.height() - 1 - y), x, *pixel);
+		}
+		assert_eq!(atlas.get_pixel(3, 0), sprite.get_pixel(0, 0));
+		assert_eq!(atlas.get_pixel(1, 0), sprite.get_pixel(0, 2));
+
+		let frames = load_sheet(
+			"rotated",
+			&atlas,
+			"{\"frames\":{\"a.png\":{\"frame\":{\"x\":1,\"y\":0,\"w\":2,\"h\":3},\"rotated\":true,\
+			\"trimmed\":true,\"spriteSourceSize\":{\"x\":1,\"y\":2,\"w\":2,\"h\":3},\
+			\"sourceSize\":{\"w\":4,\"h\":6},\"duration\":250}},\
+			\"meta\":{\"image\":\"sheet.png\"}}",
+		);
+		assert_eq!(frames.len(), 1);
+		let frame = &frames[0];
+		assert_eq!(frame.duration, 0.25);
+		assert_eq!(frame.image.dimensions(), (4, 6));
+		for (x, y, pixel) in frame.image.enumerate_pixels() {
+			let expected = match (x.checked_sub(1), y.checked_sub(2)) {
+				(Some(sx @ 0..2), Some(sy @ 0..3)) => *sprite.get_pixel(sx, sy),
+				_ => Rgba([0, 0, 0, 0]),
+			};
+			assert_eq!(*pixel, expected, "pixel {x},{y}");
+		}
+	}
+
+	#[test]
+	fn keeps_aseprite_frames_in_file_order() {
+		let sprite = sprite();
+		let mut atlas = RgbaImage::new(4, 3);
+		image::imageops::replace(&mut atlas, &sprite, 2, 0);
+		let frames = load_sheet(
+			"ordered",
+			&atlas,
+			"{\"frames\":{\
+			\"b 0.aseprite\":{\"frame\":{\"x\":2,\"y\":0,\"w\":2,\"h\":3},\"duration\":100},\
+			\"a 1.aseprite\":{\"frame\":{\"x\":0,\"y\":0,\"w\":2,\"h\":3},\"duration\":50}},\
+			\"meta\":{\"image\":\"sheet.png\"}}",
+		);
+		assert_eq!(frames.len(), 2);
+		assert_eq!(frames[0].image, sprite);
+		assert_eq!(frames[0].duration, 0.1);
+		assert_eq!(frames[1].image, RgbaImage::new(2, 3));
+		assert_eq!(frames[1].duration, 0.05);
+	}
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use rand::prelude::SliceRandom as _;
+
+use super::config;
+
+pub mod sink;
+
+pub use sink::{FileSink, NullSink, Sink};
+
+/// A fully decoded sound, stored as interleaved `f32` samples.
+#[derive(Debug, Clone)]
+pub struct Clip {
+	pub samples: Vec<f32>,
+	pub channels: u16,
+	pub sample_rate: u32,
+}
+
+impl Clip {
+	pub fn decode_ogg(path: &str) -> Result<Self, String> {
+		let file = File::open(path).map_err(|e| format!("Failed to open sound: {}", e))?;
+		let mut reader = lewton::inside_ogg::OggStreamReader::new(file)
+			.map_err(|e| format!("Failed to read ogg stream: {}", e))?;
+
+		let channels = reader.ident_hdr.audio_channels as u16;
+		let sample_rate = reader.ident_hdr.audio_sample_rate;
+
+		let mut samples = Vec::new();
+		while let Some(packet) = reader
+			.read_dec_packet_itl()
+			.map_err(|e| format!("Failed to decode ogg packet: {}", e))?
+		{
+			samples.extend(packet.iter().map(|&s| s as f32 / i16::MAX as f32));
+		}
+
+		Ok(Self {
+			samples,
+			channels,
+			sample_rate,
+		})
+	}
+
+	pub fn frames(&self) -> usize {
+		self.samples.len() / self.channels.max(1) as usize
+	}
+
+	/// Converts the clip to the given channel count and sample rate using
+	/// linear interpolation, which is plenty for short talk blips.
+	pub fn convert(&self, channels: u16, sample_rate: u32) -> Vec<f32> {
+		let in_channels = self.channels.max(1) as usize;
+		let out_channels = channels.max(1) as usize;
+		let in_frames = self.frames();
+		if in_frames == 0 {
+			return Vec::new();
+		}
+
+		let ratio = self.sample_rate as f64 / sample_rate as f64;
+		let out_frames = (in_frames as f64 / ratio).floor() as usize;
+
+		let sample = |frame: usize, channel: usize| -> f32 {
+			let frame = frame.min(in_frames - 1);
+			if in_channels == 1 {
+				self.samples[frame]
+			} else if out_channels == 1 {
+				let start = frame * in_channels;
+				self.samples[start..start + in_channels].iter().sum::<f32>() / in_channels as f32
+			} else {
+				self.samples[frame * in_channels + channel.min(in_channels - 1)]
+			}
+		};
+
+		let mut out = Vec::with_capacity(out_frames * out_channels);
+		for i in 0..out_frames {
+			let pos = i as f64 * ratio;
+			let frame = pos.floor() as usize;
+			let t = (pos - frame as f64) as f32;
+			for channel in 0..out_channels {
+				let a = sample(frame, channel);
+				let b = sample(frame + 1, channel);
+				out.push(a + (b - a) * t);
+			}
+		}
+		out
+	}
+}
+
+pub struct Audio {
+	sink: Box<dyn Sink>,
+	master_volume: f32,
+	clips: HashMap<String, Rc<Clip>>,
+}
+
+impl Audio {
+	/// Opens the configured output. The device output falls back to a silent
+	/// sink when there is no device (or when built without `device-audio`).
+	pub fn new(config: &config::Sound) -> Self {
+		let sink: Box<dyn Sink> = match config.output {
+			config::SoundOutput::Device => sink::default_sink(),
+			config::SoundOutput::Null => Box::new(NullSink),
+			config::SoundOutput::File(ref path) => Box::new(FileSink::new(path.clone())),
+		};
+		Self::with_sink(sink, config)
+	}
+
+	pub fn with_sink(sink: Box<dyn Sink>, config: &config::Sound) -> Self {
+		Self {
+			sink,
+			master_volume: config.master_volume.clamp(0.0, 1.0),
+			clips: HashMap::new(),
+		}
+	}
+
+	fn clip(&mut self, path: &str) -> Result<Rc<Clip>, String> {
+		if let Some(clip) = self.clips.get(path) {
+			return Ok(clip.clone());
+		}
+		let clip = Rc::new(Clip::decode_ogg(path)?);
+		self.clips.insert(path.to_string(), clip.clone());
+		Ok(clip)
+	}
+
+	pub fn set_master_volume(&mut self, volume: f32) {
+		self.master_volume = volume.clamp(0.0, 1.0);
+	}
+
+	pub fn play(&mut self, path: &str) {
+		if self.master_volume <= 0.0 {
+			return;
+		}
+		match self.clip(path) {
+			Ok(clip) => self.sink.play(&clip, self.master_volume),
+			Err(err) => tracing::warn!("failed to play {path}: {err}"),
+		}
+	}
+
+	pub fn play_random(&mut self, paths: &[String]) {
+		if let Some(path) = paths.choose(&mut rand::thread_rng()) {
+			self.play(path);
+		}
+	}
+}
+
+/// Default talk sounds shared by buddies that don't ship their own.
+pub fn default_talk_sounds() -> Vec<String> {
+	talk_sounds("talk")
+}
+
+/// Lists `assets/sfx/{prefix}1.ogg` through `{prefix}8.ogg`.
+pub fn talk_sounds(prefix: &str) -> Vec<String> {
+	(1..=8)
+		.map(|i| {
+			PathBuf::from("assets/sfx")
+				.join(format!("{}{}.ogg", prefix, i))
+				.to_string_lossy()
+				.into_owned()
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn clip(samples: &[f32], channels: u16, sample_rate: u32) -> Clip {
+		Clip {
+			samples: samples.to_vec(),
+			channels,
+			sample_rate,
+		}
+	}
+
+	#[test]
+	fn mono_is_copied_to_every_channel() {
+		let mono = clip(&[0.1, 0.2, 0.3], 1, 100);
+		assert_eq!(mono.convert(2, 100), [0.1, 0.1, 0.2, 0.2, 0.3, 0.3]);
+	}
+
+	#[test]
+	fn stereo_is_averaged_down_to_mono() {
+		let stereo = clip(&[0.2, 0.4, -1.0, 0.0], 2, 100);
+		assert_eq!(stereo.convert(1, 100), [0.3, -0.5]);
+	}
+
+	#[test]
+	fn resampling_interpolates_between_frames() {
+		let ramp = clip(&[0.0, 1.0, 2.0, 3.0], 1, 100);
+		assert_eq!(
+			ramp.convert(1, 200),
+			[0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.0]
+		);
+		assert_eq!(ramp.convert(1, 50), [0.0, 2.0]);
+	}
+
+	#[test]
+	fn decoded_clips_keep_their_length_when_converted() {
+		let clip = Clip::decode_ogg("assets/sfx/talk1.ogg").unwrap();
+		let converted = clip.convert(2, clip.sample_rate * 2);
+		assert_eq!(converted.len(), clip.frames() * 2 * 2);
+	}
+}
@@ -0,0 +1,289 @@
+use std::{
+	fs::File,
+	io::{Seek, SeekFrom, Write},
+	path::PathBuf,
+};
+
+use super::Clip;
+
+/// Somewhere decoded clips end up: a sound card, a file or nowhere at all.
+pub trait Sink {
+	fn play(&mut self, clip: &Clip, volume: f32);
+}
+
+/// Discards everything, used when no output device is available.
+pub struct NullSink;
+
+impl Sink for NullSink {
+	fn play(&mut self, _: &Clip, _: f32) {}
+}
+
+/// Appends every played clip to a 16-bit PCM WAV file, mostly useful for
+/// checking what would have been heard on a machine without a sound card.
+pub struct FileSink {
+	path: PathBuf,
+	channels: u16,
+	sample_rate: u32,
+	/// Created with its header when the first clip plays.
+	file: Option<File>,
+	/// Bytes of samples written so far.
+	data_len: u32,
+}
+
+impl FileSink {
+	pub const CHANNELS: u16 = 2;
+	pub const SAMPLE_RATE: u32 = 44100;
+	const HEADER_LEN: u32 = 44;
+
+	pub fn new(path: PathBuf) -> Self {
+		Self {
+			path,
+			channels: Self::CHANNELS,
+			sample_rate: Self::SAMPLE_RATE,
+			file: None,
+			data_len: 0,
+		}
+	}
+
+	fn header(&self) -> Vec<u8> {
+		let block_align = self.channels * 2;
+		let byte_rate = self.sample_rate * block_align as u32;
+
+		let mut bytes = Vec::with_capacity(Self::HEADER_LEN as usize);
+		bytes.extend_from_slice(b"RIFF");
+		bytes.extend_from_slice(&(Self::HEADER_LEN - 8 + self.data_len).to_le_bytes());
+		bytes.extend_from_slice(b"WAVEfmt ");
+		bytes.extend_from_slice(&16u32.to_le_bytes());
+		bytes.extend_from_slice(&1u16.to_le_bytes());
+		bytes.extend_from_slice(&self.channels.to_le_bytes());
+		bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+		bytes.extend_from_slice(&byte_rate.to_le_bytes());
+		bytes.extend_from_slice(&block_align.to_le_bytes());
+		bytes.extend_from_slice(&16u16.to_le_bytes());
+		bytes.extend_from_slice(b"data");
+		bytes.extend_from_slice(&self.data_len.to_le_bytes());
+		bytes
+	}
+
+	/// Appends `samples` and rewrites the header's sizes, so the file is a
+	/// complete WAV after every clip. A failed write starts a new file next time.
+	fn append(&mut self, samples: &[f32]) -> std::io::Result<()> {
+		let bytes: Vec<u8> = samples
+			.iter()
+			.flat_map(|sample| ((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+			.collect();
+
+		if self.file.is_none() {
+			self.file = Some(File::create(&self.path)?);
+			self.data_len = 0;
+		}
+		let offset = Self::HEADER_LEN + self.data_len;
+		self.data_len = u32::try_from(bytes.len())
+			.ok()
+			.and_then(|len| offset.checked_add(len))
+			.map(|end| end - Self::HEADER_LEN)
+			.ok_or_else(|| std::io::Error::other("WAV files can't be larger than 4 GiB"))?;
+		let header = self.header();
+
+		let file = self.file.as_mut().unwrap();
+		let written = file
+			.seek(SeekFrom::Start(offset as u64))
+			.and_then(|_| file.write_all(&bytes))
+			.and_then(|_| file.seek(SeekFrom::Start(0)))
+			.and_then(|_| file.write_all(&header));
+		if written.is_err() {
+			self.file = None;
+		}
+		written
+	}
+}
+
+impl Sink for FileSink {
+	fn play(&mut self, clip: &Clip, volume: f32) {
+		let samples: Vec<f32> = clip
+			.convert(self.channels, self.sample_rate)
+			.into_iter()
+			.map(|s| s * volume)
+			.collect();
+		if let Err(err) = self.append(&samples) {
+			tracing::warn!("failed to write {}: {err}", self.path.display());
+		}
+	}
+}
+
+#[cfg(feature = "device-audio")]
+pub use device::DeviceSink;
+
+#[cfg(feature = "device-audio")]
+mod device {
+	use std::sync::{Arc, Mutex};
+
+	use cpal::traits::{DeviceTrait as _, HostTrait as _, StreamTrait as _};
+
+	use super::{Clip, Sink};
+
+	struct Voice {
+		samples: Vec<f32>,
+		position: usize,
+	}
+
+	/// Mixes clips into the default output device of the default cpal host.
+	pub struct DeviceSink {
+		voices: Arc<Mutex<Vec<Voice>>>,
+		channels: u16,
+		sample_rate: u32,
+		_stream: cpal::Stream,
+	}
+
+	impl DeviceSink {
+		pub fn new() -> Result<Self, String> {
+			let host = cpal::default_host();
+			let device = host
+				.default_output_device()
+				.ok_or_else(|| "no output device available".to_string())?;
+			let supported = device
+				.default_output_config()
+				.map_err(|e| format!("failed to get output config: {}", e))?;
+			let format = supported.sample_format();
+			let config: cpal::StreamConfig = supported.into();
+
+			let voices: Arc<Mutex<Vec<Voice>>> = Arc::new(Mutex::new(Vec::new()));
+			let mixer_voices = voices.clone();
+			let stream = match format {
+				cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, mixer_voices),
+				cpal::SampleFormat::F64 => build_stream::<f64>(&device, &config, mixer_voices),
+				cpal::SampleFormat::I8 => build_stream::<i8>(&device, &config, mixer_voices),
+				cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, mixer_voices),
+				cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, mixer_voices),
+				cpal::SampleFormat::U8 => build_stream::<u8>(&device, &config, mixer_voices),
+				cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, mixer_voices),
+				cpal::SampleFormat::U32 => build_stream::<u32>(&device, &config, mixer_voices),
+				format => return Err(format!("unsupported sample format {format}")),
+			}
+			.map_err(|e| format!("failed to build output stream: {}", e))?;
+			stream
+				.play()
+				.map_err(|e| format!("failed to start output stream: {}", e))?;
+
+			Ok(Self {
+				voices,
+				channels: config.channels,
+				sample_rate: config.sample_rate.0,
+				_stream: stream,
+			})
+		}
+	}
+
+	/// A stream writing mixed voices in the device's own sample format `T`.
+	fn build_stream<T>(
+		device: &cpal::Device,
+		config: &cpal::StreamConfig,
+		voices: Arc<Mutex<Vec<Voice>>>,
+	) -> Result<cpal::Stream, cpal::BuildStreamError>
+	where
+		T: cpal::SizedSample + cpal::FromSample<f32>,
+	{
+		let mut mixed = Vec::new();
+		device.build_output_stream(
+			config,
+			move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+				mixed.clear();
+				mixed.resize(data.len(), 0.0);
+				mix(&mut voices.lock().unwrap(), &mut mixed);
+				for (out, &sample) in data.iter_mut().zip(&mixed) {
+					*out = T::from_sample(sample.clamp(-1.0, 1.0));
+				}
+			},
+			|err| tracing::error!("audio stream error: {err}"),
+			None,
+		)
+	}
+
+	/// Adds the next samples of every voice into `data`, dropping finished voices.
+	fn mix(voices: &mut Vec<Voice>, data: &mut [f32]) {
+		for voice in voices.iter_mut() {
+			let remaining = &voice.samples[voice.position..];
+			let len = remaining.len().min(data.len());
+			for (out, sample) in data.iter_mut().zip(&remaining[..len]) {
+				*out += sample;
+			}
+			voice.position += len;
+		}
+		voices.retain(|voice| voice.position < voice.samples.len());
+	}
+
+	impl Sink for DeviceSink {
+		fn play(&mut self, clip: &Clip, volume: f32) {
+			let samples = clip
+				.convert(self.channels, self.sample_rate)
+				.into_iter()
+				.map(|s| s * volume)
+				.collect();
+			self.voices.lock().unwrap().push(Voice {
+				samples,
+				position: 0,
+			});
+		}
+	}
+}
+
+#[cfg(feature = "device-audio")]
+pub fn default_sink() -> Box<dyn Sink> {
+	match DeviceSink::new() {
+		Ok(sink) => Box::new(sink),
+		Err(err) => {
+			tracing::warn!("failed to open audio device, sound disabled: {err}");
+			Box::new(NullSink)
+		}
+	}
+}
+
+#[cfg(not(feature = "device-audio"))]
+pub fn default_sink() -> Box<dyn Sink> {
+	tracing::warn!(
+		"this build has no sound device support, talk sounds are disabled. Rebuild with \
+		 `--features device-audio` to hear them, or set sound.output to \"null\" to silence this"
+	);
+	Box::new(NullSink)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn u16_at(bytes: &[u8], at: usize) -> u16 {
+		u16::from_le_bytes([bytes[at], bytes[at + 1]])
+	}
+
+	fn u32_at(bytes: &[u8], at: usize) -> u32 {
+		u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+	}
+
+	#[test]
+	fn file_sink_appends_clips_to_a_wav() {
+		let path = std::env::temp_dir().join(format!("funfriend-sink-{}.wav", std::process::id()));
+		let clip = Clip::decode_ogg("assets/sfx/talk1.ogg").unwrap();
+		let samples = clip
+			.convert(FileSink::CHANNELS, FileSink::SAMPLE_RATE)
+			.len();
+
+		let mut sink = FileSink::new(path.clone());
+		sink.play(&clip, 1.0);
+		sink.play(&clip, 0.5);
+		let bytes = std::fs::read(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(&bytes[..4], b"RIFF");
+		assert_eq!(&bytes[8..16], b"WAVEfmt ");
+		assert_eq!(u16_at(&bytes, 20), 1, "PCM");
+		assert_eq!(u16_at(&bytes, 22), FileSink::CHANNELS);
+		assert_eq!(u32_at(&bytes, 24), FileSink::SAMPLE_RATE);
+		assert_eq!(u16_at(&bytes, 34), 16, "bits per sample");
+		assert_eq!(&bytes[36..40], b"data");
+
+		let data_len = u32_at(&bytes, 40) as usize;
+		assert_eq!(data_len, samples * 2 * 2);
+		assert_eq!(bytes.len(), 44 + data_len);
+		assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+	}
+}
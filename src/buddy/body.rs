@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use super::{
+	super::texture::{SizedTexture, TextureBasket},
+	AnimationState,
+};
+
+/// The body's animations for every [`AnimationState`], and the transitions
+/// played between them.
+pub struct Body {
+	default: TextureBasket,
+	states: HashMap<AnimationState, TextureBasket>,
+	transitions: Vec<(Option<AnimationState>, AnimationState, TextureBasket)>,
+	state: AnimationState,
+	/// Index into `transitions` of the one playing right now.
+	transition: Option<usize>,
+}
+
+impl Body {
+	/// A body playing `default` in every state.
+	pub fn new(default: TextureBasket) -> Self {
+		Self {
+			default,
+			states: HashMap::new(),
+			transitions: Vec::new(),
+			state: AnimationState::Idle,
+			transition: None,
+		}
+	}
+
+	pub fn set_animation(&mut self, state: AnimationState, animation: TextureBasket) {
+		self.states.insert(state, animation);
+	}
+
+	pub fn add_transition(
+		&mut self,
+		from: Option<AnimationState>,
+		to: AnimationState,
+		animation: TextureBasket,
+	) {
+		self.transitions.push((from, to, animation));
+	}
+
+	/// Switches to `state`, through a transition if there is one. A transition from
+	/// this exact state wins over one from any state.
+	pub fn set_state(&mut self, state: AnimationState) {
+		if state == self.state {
+			return;
+		}
+		tracing::debug!("animation state {:?} -> {:?}", self.state, state);
+
+		let from = self.state;
+		self.transition = self
+			.transitions
+			.iter()
+			.position(|(f, t, _)| *f == Some(from) && *t == state)
+			.or_else(|| {
+				self.transitions
+					.iter()
+					.position(|(f, t, _)| f.is_none() && *t == state)
+			});
+		if let Some(i) = self.transition {
+			self.transitions[i].2.t = 0.0;
+		}
+
+		// Keep states sharing the default animation from restarting it.
+		let restart = self.states.contains_key(&from) || self.states.contains_key(&state);
+		self.state = state;
+		if restart {
+			self.current_mut().t = 0.0;
+		}
+	}
+
+	pub fn update(&mut self, dt: f64) {
+		if let Some(i) = self.transition {
+			let transition = &mut self.transitions[i].2;
+			transition.update(dt);
+			// The state's own animation starts from its first frame once this ends.
+			if transition.finished() {
+				self.transition = None;
+			}
+			return;
+		}
+		self.current_mut().update(dt);
+	}
+
+	pub fn texture(&self) -> &SizedTexture {
+		match self.transition {
+			Some(i) => self.transitions[i].2.texture(),
+			None => self.current().texture(),
+		}
+	}
+
+	/// Deletes the textures of every animation.
+	pub fn clean_up(&self) {
+		self.default.clean_up();
+		for animation in self.states.values() {
+			animation.clean_up();
+		}
+		for (_, _, animation) in &self.transitions {
+			animation.clean_up();
+		}
+	}
+
+	fn current(&self) -> &TextureBasket {
+		self.states.get(&self.state).unwrap_or(&self.default)
+	}
+
+	fn current_mut(&mut self) -> &mut TextureBasket {
+		self.states
+			.get_mut(&self.state)
+			.unwrap_or(&mut self.default)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `count` frames of `duration` seconds, with texture names counting up from `first`.
+	fn basket(first: u32, count: u32, duration: f64) -> TextureBasket {
+		let textures = (first..first + count)
+			.map(|tex| SizedTexture {
+				tex,
+				width: 1,
+				height: 1,
+			})
+			.collect();
+		TextureBasket::new(textures, vec![duration; count as usize])
+	}
+
+	fn sleepy_body() -> Body {
+		let mut body = Body::new(basket(0, 4, 0.1));
+		body.set_animation(AnimationState::Sleep, basket(100, 2, 0.5));
+		body.add_transition(
+			Some(AnimationState::Idle),
+			AnimationState::Sleep,
+			basket(200, 3, 0.1),
+		);
+		body.add_transition(None, AnimationState::Held, basket(300, 1, 0.1));
+		body
+	}
+
+	#[test]
+	fn falls_asleep_and_wakes_up() {
+		let mut body = sleepy_body();
+		body.update(0.25);
+		assert_eq!(body.texture().tex, 2);
+
+		body.set_state(AnimationState::Sleep);
+		let mut shown = Vec::new();
+		for _ in 0..5 {
+			shown.push(body.texture().tex);
+			body.update(0.1);
+		}
+		assert_eq!(shown, [200, 201, 202, 100, 100]);
+
+		// Picked up: the any-state transition plays, then the default restarts.
+		body.set_state(AnimationState::Held);
+		assert_eq!(body.texture().tex, 300);
+		body.update(0.1);
+		assert_eq!(body.texture().tex, 0);
+	}
+
+	#[test]
+	fn states_sharing_the_default_keep_playing_it() {
+		let mut body = sleepy_body();
+		body.update(0.25);
+		body.set_state(AnimationState::Talk);
+		assert_eq!(body.texture().tex, 2);
+		body.set_state(AnimationState::Walk);
+		assert_eq!(body.texture().tex, 2);
+	}
+
+	#[test]
+	fn held_and_talking_win_over_moving_and_sleeping() {
+		use AnimationState::*;
+		assert_eq!(AnimationState::pick(true, true, true, true), Held);
+		assert_eq!(AnimationState::pick(false, true, true, true), Talk);
+		assert_eq!(AnimationState::pick(false, false, true, true), Walk);
+		assert_eq!(AnimationState::pick(false, false, false, true), Sleep);
+		assert_eq!(AnimationState::pick(false, false, false, false), Idle);
+	}
+}
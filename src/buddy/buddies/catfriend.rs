@@ -0,0 +1,27 @@
+use super::super::{super::animation, dialog, BuddyDefinition, DialogKind};
+
+#[derive(Clone)]
+pub struct Catfriend;
+
+impl BuddyDefinition for Catfriend {
+	fn name(&self) -> &str {
+		"CATFRIEND"
+	}
+
+	fn dialog(&self, kind: DialogKind) -> Vec<Vec<String>> {
+		dialog::builtin(include_str!("../../../assets/dialog/catfriend.json"), kind)
+	}
+
+	fn animation(&self) -> animation::Source {
+		animation::Source::Files {
+			paths: (0..40)
+				.map(|i| format!("assets/buddies/catfriend_{:02}.png", i))
+				.collect(),
+			fps: 12.0,
+		}
+	}
+
+	fn font(&self) -> &str {
+		"assets/fonts/SpaceMono"
+	}
+}
@@ -0,0 +1,38 @@
+use super::super::{
+	super::{animation, audio},
+	dialog, BuddyDefinition, DialogKind,
+};
+
+#[derive(Clone)]
+pub struct God;
+
+impl BuddyDefinition for God {
+	fn name(&self) -> &str {
+		"GOD"
+	}
+
+	fn dialog(&self, kind: DialogKind) -> Vec<Vec<String>> {
+		dialog::builtin(include_str!("../../../assets/dialog/god.json"), kind)
+	}
+
+	fn animation(&self) -> animation::Source {
+		animation::Source::Files {
+			paths: (0..10)
+				.map(|i| format!("assets/buddies/god_{:02}.png", i))
+				.collect(),
+			fps: 6.0,
+		}
+	}
+
+	fn background_path(&self) -> Option<String> {
+		Some("assets/buddies/god_bg.png".to_string())
+	}
+
+	fn talk_sounds(&self) -> Vec<String> {
+		audio::talk_sounds("talk_god")
+	}
+
+	fn font(&self) -> &str {
+		"assets/fonts/Newspaper"
+	}
+}
@@ -1,15 +1,9 @@
+pub mod catfriend;
 pub mod funfriend;
+pub mod god;
+pub mod pack;
 
+pub use catfriend::Catfriend;
 pub use funfriend::Funfriend;
-
-pub trait QuickDialogInstantiation {
-	fn cloned(&self) -> Vec<Vec<String>>;
-}
-
-impl QuickDialogInstantiation for &'static [&'static [&'static str]] {
-	fn cloned(&self) -> Vec<Vec<String>> {
-		self.iter()
-			.map(|d| d.iter().map(|s| s.to_string()).collect())
-			.collect()
-	}
-}
+pub use god::God;
+pub use pack::PackBuddy;
@@ -0,0 +1,590 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::super::{
+	super::{animation, audio, config},
+	AnimationState, BuddyDefinition, DialogKind, Transition, DEFAULT_FONT_SIZE,
+};
+
+const MANIFEST_FILENAME: &str = "buddy.json";
+const DEFAULT_FONT: &str = "assets/fonts/SpaceMono";
+const DEFAULT_FPS: f64 = 10.0;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Frames {
+	Glob(String),
+	List(Vec<String>),
+	/// An Aseprite-style JSON frame map of a sprite sheet, optionally only the
+	/// frames of one of its tags.
+	Sheet {
+		sheet: String,
+		#[serde(default)]
+		tag: Option<String>,
+	},
+	/// An animated GIF, APNG or WebP.
+	Animated {
+		animated: String,
+	},
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TransitionManifest {
+	/// Any state when left out.
+	#[serde(default)]
+	pub from: Option<AnimationState>,
+	pub to: AnimationState,
+	pub frames: Frames,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+	pub name: String,
+	pub frames: Frames,
+	/// Only used for `Glob` and `List` frames, the others carry their own timing.
+	#[serde(default = "default_fps")]
+	pub fps: f64,
+	/// Animations for states that shouldn't play `frames`.
+	#[serde(default)]
+	pub animations: HashMap<AnimationState, Frames>,
+	#[serde(default)]
+	pub transitions: Vec<TransitionManifest>,
+	#[serde(default)]
+	pub background: Option<String>,
+	/// Fragment shader files, see `funfriend.frag` and `nop.frag` for the uniforms.
+	#[serde(default)]
+	pub shader: Option<String>,
+	#[serde(default)]
+	pub background_shader: Option<String>,
+	#[serde(default)]
+	pub font: Option<String>,
+	/// Pixel size for TrueType/OpenType fonts.
+	#[serde(default)]
+	pub font_size: Option<f32>,
+	/// Fonts to take chars from that `font` doesn't have, before the default fallback.
+	#[serde(default)]
+	pub fallback_fonts: Vec<String>,
+	#[serde(default)]
+	pub talk_sounds: Vec<String>,
+	#[serde(default)]
+	pub dialog: HashMap<DialogKind, Vec<Vec<String>>>,
+}
+
+fn default_fps() -> f64 {
+	DEFAULT_FPS
+}
+
+/// A buddy described by a `buddy.json` manifest inside a pack directory. Every
+/// file the manifest names is relative to the directory and has to be inside it.
+pub struct PackBuddy {
+	pub root: PathBuf,
+	pub manifest: Manifest,
+	animation: animation::Source,
+	animations: HashMap<AnimationState, animation::Source>,
+	transitions: Vec<Transition>,
+	background: Option<String>,
+	shader: Option<String>,
+	background_shader: Option<String>,
+	talk_sounds: Vec<String>,
+	font: String,
+	fallback_fonts: Vec<String>,
+}
+
+impl PackBuddy {
+	pub fn load(root: &Path) -> Result<Self, String> {
+		let manifest_path = root.join(MANIFEST_FILENAME);
+		let contents = std::fs::read_to_string(&manifest_path)
+			.map_err(|e| format!("failed to read {}: {}", manifest_path.display(), e))?;
+		let manifest: Manifest = serde_json::from_str(&contents)
+			.map_err(|e| format!("failed to parse {}: {}", manifest_path.display(), e))?;
+		Self::from_manifest(root, manifest)
+	}
+
+	/// Finds every file `manifest` names in the pack at `root`, failing if any is missing.
+	fn from_manifest(root: &Path, manifest: Manifest) -> Result<Self, String> {
+		let file = |file: &String| pack_file(root, file);
+		let source = |frames: &Frames| source(root, frames, manifest.fps);
+
+		let animation = source(&manifest.frames)?;
+		let animations = manifest
+			.animations
+			.iter()
+			.map(|(&state, frames)| Ok((state, source(frames)?)))
+			.collect::<Result<_, String>>()?;
+		let transitions = manifest
+			.transitions
+			.iter()
+			.map(|transition| {
+				Ok(Transition {
+					from: transition.from,
+					to: transition.to,
+					animation: source(&transition.frames)?,
+				})
+			})
+			.collect::<Result<_, String>>()?;
+
+		let font = match manifest.font {
+			Some(ref font) => resolve_font(root, font)?,
+			None => DEFAULT_FONT.to_string(),
+		};
+		let fallback_fonts = manifest
+			.fallback_fonts
+			.iter()
+			.map(|font| resolve_font(root, font))
+			.collect::<Result<_, _>>()?;
+
+		Ok(Self {
+			root: root.to_path_buf(),
+			animation,
+			animations,
+			transitions,
+			background: manifest.background.as_ref().map(file).transpose()?,
+			shader: manifest.shader.as_ref().map(file).transpose()?,
+			background_shader: manifest.background_shader.as_ref().map(file).transpose()?,
+			talk_sounds: manifest
+				.talk_sounds
+				.iter()
+				.map(file)
+				.collect::<Result<_, _>>()?,
+			font,
+			fallback_fonts,
+			manifest,
+		})
+	}
+
+	/// Resolves a pack reference from the config, either a path to a pack
+	/// directory or the name of a pack in one of the pack search directories.
+	pub fn find(reference: &str) -> Result<Self, String> {
+		Self::find_in(reference, &search_dirs())
+	}
+
+	fn find_in(reference: &str, dirs: &[PathBuf]) -> Result<Self, String> {
+		let path = PathBuf::from(reference);
+		if path.join(MANIFEST_FILENAME).exists() {
+			return Self::load(&path);
+		}
+
+		for dir in dirs {
+			let candidate = dir.join(reference);
+			if candidate.join(MANIFEST_FILENAME).exists() {
+				return Self::load(&candidate);
+			}
+		}
+
+		Err(format!("no buddy pack named \"{}\" found", reference))
+	}
+}
+
+impl BuddyDefinition for PackBuddy {
+	fn name(&self) -> &str {
+		&self.manifest.name
+	}
+
+	fn dialog(&self, kind: DialogKind) -> Vec<Vec<String>> {
+		self.manifest.dialog.get(&kind).cloned().unwrap_or_default()
+	}
+
+	/// `dialog.{locale}.json` next to the manifest, for translations that ship with the pack.
+	fn dialog_files(&self) -> Vec<PathBuf> {
+		let mut files = super::super::dialog::default_files(self.name());
+		files.insert(1, self.root.join("dialog"));
+		files
+	}
+
+	fn animation(&self) -> animation::Source {
+		self.animation.clone()
+	}
+
+	fn state_animation(&self, state: AnimationState) -> Option<animation::Source> {
+		self.animations.get(&state).cloned()
+	}
+
+	fn transitions(&self) -> Vec<Transition> {
+		self.transitions.clone()
+	}
+
+	fn background_path(&self) -> Option<String> {
+		self.background.clone()
+	}
+
+	fn shader(&self) -> Option<String> {
+		self.shader.clone()
+	}
+
+	fn background_shader(&self) -> Option<String> {
+		self.background_shader.clone()
+	}
+
+	fn talk_sounds(&self) -> Vec<String> {
+		if self.talk_sounds.is_empty() {
+			return audio::default_talk_sounds();
+		}
+		self.talk_sounds.clone()
+	}
+
+	fn font(&self) -> &str {
+		&self.font
+	}
+
+	fn font_size(&self) -> f32 {
+		self.manifest.font_size.unwrap_or(DEFAULT_FONT_SIZE)
+	}
+
+	fn fallback_fonts(&self) -> Vec<String> {
+		self.fallback_fonts
+			.iter()
+			.cloned()
+			.chain(std::iter::once(super::super::FALLBACK_FONT.to_string()))
+			.collect()
+	}
+}
+
+/// `file` in the pack at `root`, which has to exist and can't be outside of it.
+fn pack_file(root: &Path, file: &str) -> Result<String, String> {
+	let path = pack_path(root, file)?;
+	if path.is_file() {
+		Ok(path.to_string_lossy().into_owned())
+	} else {
+		Err(format!("pack {} has no {file}", root.display()))
+	}
+}
+
+/// `file` joined onto the pack's `root`, as long as it stays inside it.
+fn pack_path(root: &Path, file: &str) -> Result<PathBuf, String> {
+	let inside = Path::new(file)
+		.components()
+		.all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+	if inside {
+		Ok(root.join(file))
+	} else {
+		Err(format!(
+			"pack {} refers to {file}, outside of the pack",
+			root.display()
+		))
+	}
+}
+
+/// A BMFont path prefix or TrueType file in the pack.
+fn resolve_font(root: &Path, font: &str) -> Result<String, String> {
+	let path = pack_path(root, font)?;
+	if path
+		.with_file_name(format!("{}.fnt", file_name(&path)))
+		.is_file()
+		|| path.is_file()
+	{
+		Ok(path.to_string_lossy().into_owned())
+	} else {
+		Err(format!("pack {} has no font {font}", root.display()))
+	}
+}
+
+fn file_name(path: &Path) -> String {
+	path.file_name()
+		.map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+/// Where the frames of `frames` come from, with `Glob` and `List` frames played at `fps`.
+fn source(root: &Path, frames: &Frames, fps: f64) -> Result<animation::Source, String> {
+	let paths = match frames {
+		Frames::Sheet { sheet, tag } => {
+			return Ok(animation::Source::SpriteSheet {
+				path: pack_file(root, sheet)?,
+				tag: tag.clone(),
+			})
+		}
+		Frames::Animated { animated } => {
+			return Ok(animation::Source::Animated(pack_file(root, animated)?))
+		}
+		Frames::List(list) => list
+			.iter()
+			.map(|file| pack_file(root, file))
+			.collect::<Result<_, _>>()?,
+		Frames::Glob(pattern) => glob(root, pattern)?,
+	};
+	if paths.is_empty() {
+		return Err(format!("pack {} has no frames", root.display()));
+	}
+	Ok(animation::Source::Files { paths, fps })
+}
+
+/// Files in the pack matching `pattern`, in natural order so `frame_2.png` comes
+/// before `frame_10.png`. Wildcards only match within the file name, but the
+/// pattern can start with the directory to look in, like `frames/*.png`.
+fn glob(root: &Path, pattern: &str) -> Result<Vec<String>, String> {
+	let (dir, name_pattern) = match pattern.rsplit_once('/') {
+		Some((dir, name_pattern)) => (dir, name_pattern),
+		None => ("", pattern),
+	};
+	if dir.contains(['*', '?']) {
+		return Err(format!(
+			"{pattern}: wildcards can only be used in file names, not directories"
+		));
+	}
+	let dir = pack_path(root, dir)?;
+
+	let entries =
+		std::fs::read_dir(&dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+	let mut paths: Vec<PathBuf> = entries
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.is_file() && glob_match(name_pattern, &file_name(path)))
+		.collect();
+	paths.sort_by(|a, b| natural_cmp(&file_name(a), &file_name(b)));
+	Ok(paths
+		.iter()
+		.map(|path| path.to_string_lossy().into_owned())
+		.collect())
+}
+
+/// Compares strings with runs of digits ordered by their value.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+	let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+	loop {
+		match (a.peek(), b.peek()) {
+			(None, None) => return Ordering::Equal,
+			(None, Some(_)) => return Ordering::Less,
+			(Some(_), None) => return Ordering::Greater,
+			(Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+				let number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+					let mut digits = String::new();
+					while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+						digits.push(digit);
+					}
+					digits
+				};
+				let (x, y) = (number(&mut a), number(&mut b));
+				let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+				let ordering = (x_trimmed.len().cmp(&y_trimmed.len()))
+					.then_with(|| x_trimmed.cmp(y_trimmed))
+					.then_with(|| x.len().cmp(&y.len()));
+				if ordering != Ordering::Equal {
+					return ordering;
+				}
+			}
+			(Some(x), Some(y)) => {
+				let ordering = x.cmp(y);
+				if ordering != Ordering::Equal {
+					return ordering;
+				}
+				a.next();
+				b.next();
+			}
+		}
+	}
+}
+
+fn search_dirs() -> Vec<PathBuf> {
+	vec![
+		config::get_config_dir().join("buddies"),
+		config::get_assets_dir().join("packs"),
+	]
+}
+
+/// Matches a file name against a pattern with `*` and `?` wildcards.
+fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+
+	let (mut p, mut n) = (0, 0);
+	let mut star: Option<(usize, usize)> = None;
+	while n < name.len() {
+		if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+			p += 1;
+			n += 1;
+		} else if p < pattern.len() && pattern[p] == '*' {
+			star = Some((p, n));
+			p += 1;
+		} else if let Some((star_p, star_n)) = star {
+			p = star_p + 1;
+			n = star_n + 1;
+			star = Some((star_p, star_n + 1));
+		} else {
+			return false;
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A pack directory with `files` in it, removed again when dropped.
+	struct TempPack(PathBuf);
+
+	impl TempPack {
+		fn new(name: &str, manifest: &str, files: &[&str]) -> Self {
+			let root = std::env::temp_dir()
+				.join(format!("funfriend-pack-{}", std::process::id()))
+				.join(name);
+			for file in files {
+				let path = root.join(file);
+				std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+				std::fs::write(path, b"").unwrap();
+			}
+			std::fs::write(root.join(MANIFEST_FILENAME), manifest).unwrap();
+			Self(root)
+		}
+	}
+
+	impl Drop for TempPack {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn file_names(source: &animation::Source) -> Vec<String> {
+		let animation::Source::Files { paths, .. } = source else {
+			panic!("expected image files, got {source:?}");
+		};
+		paths
+			.iter()
+			.map(|path| file_name(Path::new(path)))
+			.collect()
+	}
+
+	#[test]
+	fn loads_the_bundled_pack() {
+		let pack = PackBuddy::find("bstrd").unwrap();
+		assert_eq!(pack.name(), "BSTRD");
+		assert_eq!(file_names(&pack.animation()), ["bstrd.png"]);
+		assert_eq!(pack.talk_sounds().len(), 8);
+		assert!(pack.font().starts_with("assets/packs/bstrd"));
+		assert!(!pack.dialog(DialogKind::Chatter).is_empty());
+	}
+
+	#[test]
+	fn parses_every_kind_of_frames() {
+		let manifest = r#"{
+			"name": "Test",
+			"frames": "idle_*.png",
+			"fps": 4,
+			"animations": {
+				"talk": ["talk.png"],
+				"sleep": { "sheet": "body.json", "tag": "sleep" },
+				"held": { "animated": "held.gif" }
+			},
+			"transitions": [{ "to": "held", "frames": "frames/startled_?.png" }],
+			"background": "bg.png",
+			"font": "font",
+			"dialog": { "chatter": [["HI"]] }
+		}"#;
+		let files = [
+			"idle_1.png",
+			"idle_2.png",
+			"talk.png",
+			"body.json",
+			"held.gif",
+			"frames/startled_1.png",
+			"bg.png",
+			"font.fnt",
+		];
+		let dir = TempPack::new("kinds", manifest, &files);
+		let pack = PackBuddy::load(&dir.0).unwrap();
+
+		let animation::Source::Files { fps, .. } = pack.animation() else {
+			panic!("expected image files");
+		};
+		assert_eq!(fps, 4.0);
+		assert_eq!(file_names(&pack.animation()), ["idle_1.png", "idle_2.png"]);
+		let talk = pack.state_animation(AnimationState::Talk).unwrap();
+		assert_eq!(file_names(&talk), ["talk.png"]);
+		assert!(matches!(
+			pack.state_animation(AnimationState::Sleep),
+			Some(animation::Source::SpriteSheet { tag: Some(tag), .. }) if tag == "sleep"
+		));
+		assert!(matches!(
+			pack.state_animation(AnimationState::Held),
+			Some(animation::Source::Animated(_))
+		));
+		assert!(pack.state_animation(AnimationState::Walk).is_none());
+
+		let [transition] = &pack.transitions()[..] else {
+			panic!("expected one transition");
+		};
+		assert_eq!(
+			(transition.from, transition.to),
+			(None, AnimationState::Held)
+		);
+		assert_eq!(file_names(&transition.animation), ["startled_1.png"]);
+		assert_eq!(
+			pack.background_path(),
+			Some(dir.0.join("bg.png").to_string_lossy().into())
+		);
+		assert_eq!(pack.font(), dir.0.join("font").to_string_lossy());
+		assert_eq!(pack.dialog(DialogKind::Chatter), [["HI"]]);
+	}
+
+	#[test]
+	fn globs_frames_in_natural_order() {
+		let files = ["frame_10.png", "frame_2.png", "frame_1.png", "frame_1.txt"];
+		let dir = TempPack::new(
+			"order",
+			r#"{ "name": "Test", "frames": "frame_*.png" }"#,
+			&files,
+		);
+		let pack = PackBuddy::load(&dir.0).unwrap();
+		assert_eq!(
+			file_names(&pack.animation()),
+			["frame_1.png", "frame_2.png", "frame_10.png"]
+		);
+	}
+
+	#[test]
+	fn matches_wildcards() {
+		assert!(glob_match("*.png", "body.png"));
+		assert!(glob_match("body_??.png", "body_01.png"));
+		assert!(glob_match("*_*", "a_b_c"));
+		assert!(!glob_match("body_?.png", "body_01.png"));
+		assert!(!glob_match("*.png", "body.png.bak"));
+		assert_eq!(natural_cmp("a2", "a10"), Ordering::Less);
+		assert_eq!(natural_cmp("a02", "a2"), Ordering::Greater);
+		assert_eq!(natural_cmp("b1", "a10"), Ordering::Greater);
+	}
+
+	#[test]
+	fn rejects_missing_files_and_files_outside_the_pack() {
+		let cases = [
+			(r#""frames": "body_*.png""#, "has no frames"),
+			(
+				r#""frames": ["body.png"], "font": "missing""#,
+				"has no font missing",
+			),
+			(
+				r#""frames": ["body.png"], "talkSounds": ["talk.ogg"]"#,
+				"has no talk.ogg",
+			),
+			(r#""frames": ["../body.png"]"#, "outside of the pack"),
+			(
+				r#""frames": ["body.png"], "font": "/usr/share/font""#,
+				"outside of the pack",
+			),
+			(r#""frames": "*/body.png""#, "only be used in file names"),
+		];
+		for (i, (fields, message)) in cases.into_iter().enumerate() {
+			let manifest = format!(r#"{{ "name": "Test", {fields} }}"#);
+			let dir = TempPack::new(&format!("bad{i}"), &manifest, &["body.png"]);
+			let err = PackBuddy::load(&dir.0).err().unwrap();
+			assert!(err.contains(message), "{fields}: {err}");
+		}
+	}
+
+	#[test]
+	fn finds_packs_by_path_or_name() {
+		let manifest = r#"{ "name": "Found", "frames": ["body.png"] }"#;
+		let dir = TempPack::new("found", manifest, &["body.png"]);
+		let search = [dir.0.parent().unwrap().to_path_buf()];
+
+		let by_name = PackBuddy::find_in("found", &search).unwrap();
+		assert_eq!(by_name.name(), "Found");
+		let by_path = PackBuddy::find_in(&dir.0.to_string_lossy(), &[]).unwrap();
+		assert_eq!(by_path.root, dir.0);
+		let err = PackBuddy::find_in("lost", &search).err().unwrap();
+		assert!(err.contains("no buddy pack named \"lost\""), "{err}");
+	}
+}
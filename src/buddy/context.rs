@@ -4,26 +4,33 @@ use std::rc::Rc;
 use glfw::ffi::{GLFWmonitor, GLFWvidmode};
 use glfw::Context as _;
 use rand::prelude::SliceRandom;
-use rand::Rng as _;
 
 use super::{
 	super::{
-		buddy::{self, DialogKind},
+		audio::Audio,
+		buddy::{self, AnimationState, DialogKind},
 		config, ease,
+		font_manager::FontMan,
+		graphics::dialog::Dialog,
 		vec2::Vec2,
 		window::{Window, Windowed},
 	},
+	movement::{self, BuddyState, Monitor, MovementBehavior, Target},
 	BuddyDefinition,
 };
 
 const CHATTER_TIMER: f64 = 3.0;
 const STAY_STILL_AFTER_HELD: f64 = 1.0;
-const WANDER_TIMER: f64 = 4.0;
-const FOLLOW_DIST: i32 = 120;
+const FLING_MIN_SPEED: f64 = 200.0;
+const FLING_STOP_SPEED: f64 = 20.0;
+/// Seconds it takes the held velocity to catch up with about two thirds of a
+/// change in cursor speed.
+const HELD_VELOCITY_SMOOTHING: f64 = 0.025;
 
 pub struct Context {
 	pub buddy: Rc<RefCell<dyn BuddyDefinition>>,
-	pub owned_contexts: Vec<Rc<RefCell<dyn Windowed>>>,
+	pub audio: Rc<RefCell<Audio>>,
+	pub owned_contexts: Vec<Dialog>,
 	pub renderer: buddy::Renderer,
 	pub chatter_timer: f64,
 	pub chatter_index: i32,
@@ -32,25 +39,36 @@ pub struct Context {
 	pub held_at: Vec2,
 	pub started_holding_at: Vec2,
 	pub held_timer: f64,
+	pub held_velocity: Vec2,
+	pub fling_velocity: Vec2,
+	pub fling_friction: f64,
+	pub fling_restitution: f64,
 	pub waiting_for_stable_pos: bool,
 	pub static_pos: Vec2,
 	pub easing_from: Vec2,
 	pub easing_to: Vec2,
 	pub easing_dur: f64,
 	pub easing_t: f64,
-	pub wander_timer: f64,
+	pub easing_fn: fn(f64) -> f64,
 	pub window: Window,
-	pub dir_vec: Vec2,
-	pub configured_behavior: config::Behavior,
+	pub movement: Box<dyn MovementBehavior>,
 	pub speed: f64,
-	pub internal_pos: Vec2,
+	pub dialog_settings: config::Dialog,
+	pub sleep_after: f64,
+	/// Seconds since the buddy was last held or spoke.
+	pub idle_time: f64,
 }
 
 impl Context {
-	pub fn new(config: &config::Config, buddy: Rc<RefCell<dyn BuddyDefinition>>) -> Self {
+	pub fn new(
+		config: &config::Config,
+		buddy: Rc<RefCell<dyn BuddyDefinition>>,
+		audio: Rc<RefCell<Audio>>,
+		glfw: &mut glfw::Glfw,
+	) -> Self {
 		let name = format!("!!__{}__!!", buddy.borrow().name());
 
-		let mut window = Window::new(512, 512, name.as_str());
+		let mut window = Window::new(glfw, 512, 512, name.as_str());
 
 		let renderer = buddy::Renderer::new(config, buddy.clone(), &mut window);
 		let window_size = Self::get_window_size(&renderer);
@@ -59,15 +77,19 @@ impl Context {
 		window
 			.handle
 			.set_size(window_size.x as i32, window_size.y as i32);
-		window.handle.make_current();
+		window.make_current();
 		// window.window_handle.set_cursor(Some(glfw::Cursor::standard(glfw::StandardCursor::Hand)));
-		gl::load_with(|s| window.glfw.get_proc_address_raw(s) as *const _);
 
-		let binding = buddy.borrow().dialog(DialogKind::Chatter);
+		let binding = buddy::dialog::lines(
+			&*buddy.borrow(),
+			DialogKind::Chatter,
+			config.dialog.locale.as_deref(),
+		);
 		let chatter_array = binding.choose(&mut rand::thread_rng()).cloned();
 
 		let mut result = Self {
 			buddy: buddy.clone(),
+			audio,
 			owned_contexts: Vec::new(),
 			renderer,
 			chatter_timer: 1.0,
@@ -76,6 +98,10 @@ impl Context {
 			held: false,
 			held_at: Vec2::zero(),
 			held_timer: 0.0,
+			held_velocity: Vec2::zero(),
+			fling_velocity: Vec2::zero(),
+			fling_friction: config.buddy.fling_friction,
+			fling_restitution: config.buddy.fling_restitution,
 			started_holding_at: Vec2::zero(),
 			waiting_for_stable_pos: false,
 			static_pos: Vec2::zero(),
@@ -83,20 +109,13 @@ impl Context {
 			easing_to: Vec2::zero(),
 			easing_dur: 0.0,
 			easing_t: 0.0,
-			wander_timer: WANDER_TIMER,
+			easing_fn: ease::in_out_sine,
 			window,
-			dir_vec: match config.buddy.behavior {
-				config::Behavior::Normal => Vec2::zero(),
-				config::Behavior::Dvd => {
-					let mut rng = rand::thread_rng();
-					let x = rng.gen_range(-1.0..1.0);
-					let y = rng.gen_range(-1.0..1.0);
-					Vec2::new(x, y).normalize()
-				}
-			},
-			configured_behavior: config.buddy.behavior.clone(),
+			movement: movement::from_config(config.buddy.behavior),
 			speed: config.buddy.speed.clone(),
-			internal_pos: Vec2::zero(),
+			dialog_settings: config.dialog.clone(),
+			sleep_after: config.buddy.sleep_after,
+			idle_time: 0.0,
 		};
 
 		let random_position = Self::random_pos_current_monitor(&result);
@@ -105,7 +124,6 @@ impl Context {
 			.window
 			.handle
 			.set_pos(random_position.x as i32, random_position.y as i32);
-		result.internal_pos = random_position;
 		result.static_pos = random_position;
 		drop(config);
 		result
@@ -116,10 +134,14 @@ impl Context {
 	//
 	// }
 	fn get_window_size(renderer: &buddy::Renderer) -> Vec2 {
-		let size = renderer.funfriend_size();
+		Self::window_size_for(renderer.resolution)
+	}
+
+	/// Size of the window around a buddy of `size`, leaving room for it to bounce.
+	pub fn window_size_for(size: Vec2) -> Vec2 {
 		Vec2::new_i(
-			(size.0 as f64 * 1.3).floor() as i32,
-			(size.1 as f64 * 1.3).floor() as i32,
+			(size.x.trunc() * 1.3).floor() as i32,
+			(size.y.trunc() * 1.3).floor() as i32,
 		)
 	}
 
@@ -208,14 +230,55 @@ impl Context {
 	}
 
 	pub fn render(&mut self, dt: f64) {
-		self.window.handle.make_current();
-		gl::load_with(|s| self.window.glfw.get_proc_address_raw(s) as *const _);
+		self.window.make_current();
 		let window_size = Self::get_window_size(&self.renderer);
 		self.renderer
 			.render(dt, window_size.x as i32, window_size.y as i32, &self.window);
 	}
 
 	pub fn goto(&mut self, pos: Vec2, dur: f64, set_as_static: bool) {
+		self.goto_eased(pos, dur, set_as_static, ease::in_out_sine);
+	}
+
+	/// Swaps in a different buddy definition, keeping the window where it is.
+	pub fn set_buddy(&mut self, config: &config::Config, buddy: Rc<RefCell<dyn BuddyDefinition>>) {
+		self.buddy = buddy;
+		let chatter = self.dialog_lines(DialogKind::Chatter);
+		self.chatter_array = chatter.choose(&mut rand::thread_rng()).cloned();
+		self.chatter_index = 0;
+		self.chatter_timer = 1.0;
+		self.rebuild_renderer(config);
+	}
+
+	/// Recreates the renderer after the buddy or its size changed. The window is
+	/// resized around its center so the buddy stays on the same spot.
+	pub fn rebuild_renderer(&mut self, config: &config::Config) {
+		let old_size = Self::get_window_size(&self.renderer);
+		self.window.make_current();
+		self.renderer.clean_up();
+		self.renderer = buddy::Renderer::new(config, self.buddy.clone(), &mut self.window);
+		let new_size = Self::get_window_size(&self.renderer);
+
+		let offset = (old_size - new_size) / 2.0;
+		let position = Vec2::new_t(self.window.handle.get_pos()) + offset;
+		self.window
+			.handle
+			.set_size(new_size.x as i32, new_size.y as i32);
+		self.window
+			.handle
+			.set_pos(position.x as i32, position.y as i32);
+		self.static_pos += offset;
+		self.easing_from += offset;
+		self.easing_to += offset;
+	}
+
+	/// Swaps out how the buddy moves on its own, e.g. from the control socket.
+	pub fn set_behavior(&mut self, behavior: config::Behavior) {
+		self.movement = movement::from_config(behavior);
+	}
+
+	pub fn goto_eased(&mut self, pos: Vec2, dur: f64, set_as_static: bool, easing: fn(f64) -> f64) {
+		self.easing_fn = easing;
 		self.easing_t = 0.0;
 		self.easing_dur = dur;
 		self.easing_from = Vec2::new(
@@ -231,186 +294,243 @@ impl Context {
 		tracing::info!("going from {:?} to {:?}", self.easing_from, self.easing_to);
 	}
 
-	pub fn update_wander(&mut self, dt: f64) {
-		// tracing::info!("dt: {}", dt);
-		if self.moving() {
-			self.easing_t += dt;
-			let a = ease::in_out_sine(self.easing_t / self.easing_dur);
-			let new_position = self.easing_from * (1.0 - a) + self.easing_to * a;
-			self.window
-				.handle
-				.set_pos(new_position.x as i32, new_position.y as i32);
-
-			self.wander_timer = WANDER_TIMER;
-		} else {
-			tracing::info!("behavior: {:?}", self.behavior());
-			match self.behavior() {
-				Behavior::Wander => {
-					self.wander_timer -= dt;
-					if self.wander_timer <= 0.0 {
-						self.goto(self.static_pos + Vec2::rand(0.0..40.0), 4.0, false)
-					}
-				}
-				Behavior::Follow => {
-					if !self.moving() {
-						let cursor_pos = self.window.handle.get_cursor_pos();
-						let mut x_target = self.window.handle.get_pos().0 as f64;
-						let mut y_target = self.window.handle.get_pos().1 as f64;
-
-						let x_dist = cursor_pos.0;
-						let y_dist = cursor_pos.1;
-
-						if x_dist.abs() > FOLLOW_DIST as f64 {
-							x_target = self.window.handle.get_pos().0 as f64 + x_dist
-								- FOLLOW_DIST as f64 * x_dist.signum();
-						}
-
-						if y_dist.abs() > FOLLOW_DIST as f64 {
-							y_target = self.window.handle.get_pos().1 as f64 + y_dist
-								- FOLLOW_DIST as f64 * y_dist.signum();
-						}
-
-						self.goto(Vec2::new(x_target, y_target), 1.0, true);
-					}
-				}
-				Behavior::Stay => {}
-			}
-		}
+	fn step_easing(&mut self, dt: f64) {
+		self.easing_t += dt;
+		let a = (self.easing_fn)((self.easing_t / self.easing_dur).min(1.0));
+		let new_position = self.easing_from * (1.0 - a) + self.easing_to * a;
+		self.window
+			.handle
+			.set_pos(new_position.x as i32, new_position.y as i32);
 	}
 
-	pub fn update_pos(&mut self, dt: f64) {
+	fn update_held(&mut self, dt: f64) {
 		let cursor_pos = self.window.handle.get_cursor_pos();
 		let cursor_pos = Vec2::new(cursor_pos.0, cursor_pos.1);
-		if self.held {
-			self.static_pos = self.static_pos - self.held_at + cursor_pos;
-			self.window
-				.handle
-				.set_pos(self.static_pos.x as i32, self.static_pos.y as i32);
-		} else {
-			self.held_timer -= dt;
-			if self.held_timer <= 0.0 {
-				self.update_wander(dt);
-
-				if self.waiting_for_stable_pos {
-					self.waiting_for_stable_pos = false;
-
-					let stable_pos_dist = self.static_pos.dist(self.started_holding_at);
-					tracing::info!("travelled {:?}", stable_pos_dist);
-					let buddy = self.buddy.borrow();
-					if !self.speaking() {
-						if stable_pos_dist > 50.0 {
-							let dialog = buddy.dialog(DialogKind::Moved);
-							drop(buddy);
-							// TODO(is2511): Make `say()` work
-							// self.say(dialog);
-						} else {
-							let dialog = buddy.dialog(DialogKind::Touched);
-							drop(buddy);
-							// self.say(dialog);
-						}
-					}
-				}
+		let delta = cursor_pos - self.held_at;
+		if dt > 0.0 {
+			// smooth out jittery cursor movement so a release after a pause doesn't fling
+			self.held_velocity = smooth_velocity(self.held_velocity, delta / dt, dt);
+		}
+		self.static_pos += delta;
+		self.window
+			.handle
+			.set_pos(self.static_pos.x as i32, self.static_pos.y as i32);
+	}
+
+	fn react_to_handling(&mut self) {
+		self.waiting_for_stable_pos = false;
+
+		let stable_pos_dist = self.static_pos.dist(self.started_holding_at);
+		tracing::info!("travelled {:?}", stable_pos_dist);
+		if !self.speaking() {
+			let kind = if stable_pos_dist > 50.0 {
+				DialogKind::Moved
 			} else {
-				self.waiting_for_stable_pos = true;
+				DialogKind::Touched
+			};
+			let dialog = self.dialog_lines(kind);
+			if let Some(text) = dialog.choose(&mut rand::thread_rng()) {
+				self.say_array(text.clone());
 			}
 		}
 	}
 
-	fn update_dvd(&mut self, dt: f64) {
-		// tracing::info!("FRAME START");
-		// tracing::info!("init pos: {:?}", self.internal_pos);
-		// tracing::info!("dir: {:?}", self.dir_vec);
+	pub fn update_pos(&mut self, dt: f64) {
+		if self.held {
+			self.update_held(dt);
+			return;
+		}
+		if self.flinging() {
+			self.update_fling(dt);
+			self.held_timer = STAY_STILL_AFTER_HELD;
+			self.waiting_for_stable_pos = true;
+			return;
+		}
 
-		let cursor_pos = self.window.handle.get_cursor_pos();
-		let cursor_pos = Vec2::new(cursor_pos.0, cursor_pos.1);
+		self.held_timer -= dt;
+		if self.held_timer > 0.0 {
+			self.waiting_for_stable_pos = true;
+		} else if self.waiting_for_stable_pos {
+			self.react_to_handling();
+		}
 
-		if self.held {
-			tracing::info!("cursor pos: {:?}", cursor_pos);
-			tracing::info!("held at: {:?}", self.held_at);
-			tracing::info!(
-				"should set to: {:?}",
-				self.internal_pos - self.held_at + self.internal_pos
-			);
-			self.internal_pos = self.internal_pos - self.held_at + cursor_pos;
-			self.window
-				.handle
-				.set_pos(self.internal_pos.x as i32, self.internal_pos.y as i32);
+		if self.moving() {
+			self.step_easing(dt);
 			return;
 		}
-		let (_, _, _, w, h, _, _, mw, mh) =
+
+		let state = self.movement_state();
+		let monitors = Monitor::all();
+		match self.movement.update(&state, &monitors, dt) {
+			Some(Target::Goto {
+				position,
+				duration,
+				set_as_static,
+				easing,
+			}) => self.goto_eased(position, duration, set_as_static, easing),
+			Some(Target::Set(position)) => {
+				self.static_pos = position;
+				self.window
+					.handle
+					.set_pos(position.x as i32, position.y as i32);
+			}
+			None => {}
+		}
+	}
+
+	fn movement_state(&self) -> BuddyState {
+		let (monitor, ..) = Self::get_current_monitor(self.window.handle.window_ptr());
+		let cursor = self.window.handle.get_cursor_pos();
+
+		BuddyState {
+			position: Vec2::new_t(self.window.handle.get_pos()),
+			size: Vec2::new_t(self.window.handle.get_size()),
+			static_pos: self.static_pos,
+			cursor: Vec2::new(cursor.0, cursor.1),
+			speed: self.speed,
+			speaking: self.speaking(),
+			recently_held: self.held_timer > 0.0,
+			sleeping: self.sleeping(),
+			monitor: Monitor::from_ptr(monitor),
+		}
+	}
+
+	fn update_fling(&mut self, dt: f64) {
+		let (_, _, _, w, h, mx, my, mw, mh) =
 			Self::get_current_monitor(self.window.handle.window_ptr());
-		// tracing::info!("w: {}, h: {}", w, h);
 
-		self.internal_pos += self.dir_vec * self.speed * dt;
-		// tracing::info!("new pos: {:?}", self.internal_pos);
-		// tracing::info!("current window pos: {:?}", self.window.window_handle.get_pos());
+		let mut pos = self.static_pos + self.fling_velocity * dt;
+		if mw > 0 && mh > 0 {
+			(pos, self.fling_velocity) = bounce_off_edges(
+				pos,
+				self.fling_velocity,
+				Vec2::new_i(mx, my),
+				Vec2::new_i(mx + mw - w, my + mh - h),
+				self.fling_restitution,
+			);
+		}
+
+		self.fling_velocity = self.fling_velocity * (-self.fling_friction * dt).exp();
+		if self.fling_velocity.len() < FLING_STOP_SPEED {
+			self.fling_velocity = Vec2::zero();
+		}
+
+		self.static_pos = pos;
 		self.window
 			.handle
-			.set_pos(self.internal_pos.x as i32, self.internal_pos.y as i32);
-		// tracing::info!("new window pos: {:?}", self.window.window_handle.get_pos());
-		// tracing::info!("FRAME END");
+			.set_pos(self.static_pos.x as i32, self.static_pos.y as i32);
+	}
 
-		// tracing::info!("internal pos: {:?}, w: {}, h: {}", self.internal_pos, w, h);
+	pub fn say(&mut self, text: String) {
+		let buddy = self.buddy.borrow();
+		let window_pos = Vec2::new_t(self.window.handle.get_pos());
+		let window_size = Self::get_window_size(&self.renderer);
 
-		if self.internal_pos.x <= 0.0 {
-			tracing::info!("hit left wall");
-			self.dir_vec.x = -self.dir_vec.x;
-			self.internal_pos.x = 0.0;
-		}
-		if self.internal_pos.y <= 0.0 {
-			tracing::info!("hit top wall");
-			self.dir_vec.y = -self.dir_vec.y;
-			self.internal_pos.y = 0.0;
+		let sheet =
+			FontMan::load_with_fallbacks(buddy.font(), buddy.font_size(), &buddy.fallback_fonts());
+		let sheet = match sheet {
+			Ok(sheet) => sheet,
+			Err(err) => {
+				tracing::error!("failed to show dialog: {err}");
+				return;
+			}
+		};
+
+		// Older bubbles move up out of the way of the new one, which sits with its
+		// bottom edge just above the buddy.
+		let (_, _, bubble_size) = Dialog::layout(&text, &sheet);
+		for dialog in self.owned_contexts.iter_mut() {
+			dialog.bump(bubble_size.y);
 		}
-		if self.internal_pos.x + w as f64 >= mw as f64 {
-			tracing::info!("hit right wall");
-			self.dir_vec.x = -self.dir_vec.x;
-			self.internal_pos.x = (mw - w) as f64;
+		let text_position = Vec2::new(
+			window_pos.x + window_size.x / 2.0,
+			window_pos.y - 20.0 - bubble_size.y / 2.0,
+		);
+		let dialog = Dialog::new(
+			&mut self.window.handle.glfw,
+			&text,
+			sheet,
+			text_position,
+			Dialog::DEFAULT_DURATION,
+			Some((window_pos, window_size)),
+			&self.dialog_settings,
+		);
+		drop(buddy);
+
+		self.owned_contexts.push(dialog);
+	}
+
+	fn update_chatter(&mut self, dt: f64) {
+		self.chatter_timer -= dt;
+		if self.chatter_timer <= 0.0 {
+			tracing::info!("allowed to speak");
+			self.chatter_timer += CHATTER_TIMER;
+
+			if let Some(ref chatter_array) = self.chatter_array {
+				if let Some(chatter) = chatter_array.get(self.chatter_index as usize) {
+					tracing::info!("should speak from update");
+					self.say(chatter.clone());
+				}
+			}
+			self.chatter_index += 1;
 		}
-		if self.internal_pos.y + h as f64 >= mh as f64 {
-			tracing::info!("hit bottom wall");
-			self.dir_vec.y = -self.dir_vec.y;
-			self.internal_pos.y = (mh - h) as f64;
+	}
+
+	fn dialog_lines(&self, kind: DialogKind) -> Vec<Vec<String>> {
+		let locale = self.dialog_settings.locale.as_deref();
+		buddy::dialog::lines(&*self.buddy.borrow(), kind, locale)
+	}
+
+	pub fn say_array(&mut self, text: Vec<String>) {
+		self.chatter_array = Some(text);
+		self.chatter_timer = 0.0;
+		self.chatter_index = 0;
+	}
+
+	fn update_dialogs(&mut self, dt: f64) {
+		let window_pos = Vec2::new_t(self.window.handle.get_pos());
+		let window_size = Self::get_window_size(&self.renderer);
+
+		self.owned_contexts.retain_mut(|dialog| {
+			if dialog.should_close() {
+				dialog.clean_up();
+				false
+			} else {
+				dialog.set_parent(window_pos, window_size);
+				dialog.update(dt);
+				for _ in 0..dialog.take_talk_sounds() {
+					self.buddy
+						.borrow()
+						.play_talk_sound(&mut self.audio.borrow_mut());
+				}
+				true
+			}
+		});
+	}
+
+	fn update_animation(&mut self, dt: f64) {
+		if self.held || self.speaking() {
+			self.idle_time = 0.0;
+		} else {
+			self.idle_time += dt;
 		}
+		let state = self.animation_state();
+		self.renderer.body.set_state(state);
 	}
 
-	pub fn say(&mut self, text: String) {
-		// for context in Rc::get_mut(self.app_contexts.as_mut()).unwrap().into_iter().enumerate() {
-		// 	if context
-		// }
+	pub fn animation_state(&self) -> AnimationState {
+		AnimationState::pick(
+			self.held,
+			self.speaking(),
+			self.moving() || self.flinging(),
+			self.sleeping(),
+		)
 	}
 
-	// pub fn say(&mut self, text_groups: Vec<Vec<String>>) {
-	// 	let buddy = self.buddy.borrow();
-	// 	let flattened_texts: Vec<String> = text_groups.into_iter().flatten().collect();
-	// 	let window_size = Self::get_window_size(&self.renderer);
-	// 	let window_size = Vec2::new(window_size.x, window_size.y);
-	//
-	// 	let mut last_context: Option<Box<ChatterContext>> = None;
-	//
-	// 	let text_position = Vec2::new(
-	// 		self.window.window_handle.get_pos().0 as f64 + window_size.x / 2.0,
-	// 		self.window.window_handle.get_pos().1 as f64 - 20.0,
-	// 	);
-	// 	for text in flattened_texts {
-	// 		let chatter_context = ChatterContext::new(
-	// 			&text,
-	// 			&buddy.font(),
-	// 			text_position,
-	// 			ChatterContext::DEFAULT_DURATION,
-	// 			last_context.take(),
-	// 		);
-	//
-	// 		last_context = Some(Box::new(chatter_context));
-	// 		buddy.talk_sound();
-	// 	}
-	// }
-	//
-	// pub fn say_array(&mut self, text: Vec<String>) {
-	// 	self.chatter_array = Some(text);
-	// 	self.chatter_timer = 0.0;
-	// 	self.chatter_index = 0;
-	// }
+	/// Asleep buddies stop wandering until they're picked up or talk.
+	pub fn sleeping(&self) -> bool {
+		self.sleep_after > 0.0 && self.idle_time >= self.sleep_after
+	}
 
 	pub fn speaking(&self) -> bool {
 		if let Some(ref chatter_array) = self.chatter_array {
@@ -420,12 +540,8 @@ impl Context {
 		}
 	}
 
-	pub fn behavior(&self) -> Behavior {
-		if self.speaking() {
-			Behavior::Follow
-		} else {
-			Behavior::Wander
-		}
+	pub fn flinging(&self) -> bool {
+		self.fling_velocity.ne(Vec2::zero())
 	}
 
 	pub fn moving(&self) -> bool {
@@ -433,40 +549,56 @@ impl Context {
 	}
 }
 
+/// Moves `velocity` towards `sample` by how much of [`HELD_VELOCITY_SMOOTHING`]
+/// `dt` covers, so the result doesn't depend on the frame rate.
+fn smooth_velocity(velocity: Vec2, sample: Vec2, dt: f64) -> Vec2 {
+	let a = 1.0 - (-dt / HELD_VELOCITY_SMOOTHING).exp();
+	velocity * (1.0 - a) + sample * a
+}
+
+/// Keeps a thrown buddy at `pos` between `min` and `max`, reversing and damping
+/// `velocity` on every axis it hit an edge on. A window bigger than the monitor
+/// stays at the top-left edge.
+fn bounce_off_edges(
+	mut pos: Vec2,
+	mut velocity: Vec2,
+	min: Vec2,
+	max: Vec2,
+	restitution: f64,
+) -> (Vec2, Vec2) {
+	let (right, bottom) = (max.x.max(min.x), max.y.max(min.y));
+	if pos.x < min.x || pos.x > right {
+		pos.x = pos.x.clamp(min.x, right);
+		velocity.x = -velocity.x * restitution;
+	}
+	if pos.y < min.y || pos.y > bottom {
+		pos.y = pos.y.clamp(min.y, bottom);
+		velocity.y = -velocity.y * restitution;
+	}
+	(pos, velocity)
+}
+
 impl Windowed for Context {
 	fn should_close(&self) -> bool {
 		self.window.handle.should_close()
 	}
 
 	fn clean_up(&mut self) {
+		for dialog in self.owned_contexts.iter_mut() {
+			dialog.clean_up();
+		}
+		self.owned_contexts.clear();
+		// The textures belong to this window's GL context, not the last dialog's.
+		self.window.make_current();
 		self.renderer.clean_up();
 	}
 
 	fn update(&mut self, dt: f64) {
-		// tracing::info!("current behavior: {:?}", self.behavior());
-		tracing::info!("expected behavior: {:?}", self.behavior());
-		match self.configured_behavior {
-			config::Behavior::Dvd => {
-				self.update_dvd(dt);
-			}
-			config::Behavior::Normal => {
-				self.chatter_timer -= dt;
-				if self.chatter_timer <= 0.0 {
-					tracing::info!("allowed to speak");
-					self.chatter_timer += CHATTER_TIMER;
-
-					if let Some(ref chatter_array) = self.chatter_array {
-						if let Some(chatter) = chatter_array.get(self.chatter_index as usize) {
-							tracing::info!("should speak from update");
-							self.say(chatter.clone());
-						}
-					}
-					self.chatter_index += 1;
-				}
-				self.update_pos(dt);
-			}
-		}
+		self.update_chatter(dt);
+		self.update_pos(dt);
+		self.update_animation(dt);
 
+		self.update_dialogs(dt);
 		self.render(dt);
 
 		self.window.handle.swap_buffers();
@@ -483,6 +615,8 @@ impl Windowed for Context {
 			self.started_holding_at = Vec2::new_t(self.window.handle.get_pos());
 		}
 		self.held_timer = STAY_STILL_AFTER_HELD;
+		self.held_velocity = Vec2::zero();
+		self.fling_velocity = Vec2::zero();
 		self.easing_dur = 0.0;
 		self.window
 			.handle
@@ -491,15 +625,69 @@ impl Windowed for Context {
 
 	fn on_release(&mut self, _: Vec2) {
 		self.held = false;
+		if self.held_velocity.len() > FLING_MIN_SPEED {
+			self.fling_velocity = self.held_velocity;
+		}
 		self.window
 			.handle
 			.set_cursor(Some(glfw::Cursor::standard(glfw::StandardCursor::Arrow)));
 	}
 }
 
-#[derive(Debug, Clone)]
-pub enum Behavior {
-	Wander,
-	Follow,
-	Stay,
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Held velocity after dragging right at 1000px/s for 0.1s and then holding
+	/// still for `pause` seconds, at `fps` frames per second.
+	fn fling_after(pause: f64, fps: f64) -> f64 {
+		let dt = 1.0 / fps;
+		let mut velocity = Vec2::zero();
+		for _ in 0..(0.1 * fps).round() as usize {
+			velocity = smooth_velocity(velocity, Vec2::new(1000.0, 0.0), dt);
+		}
+		for _ in 0..(pause * fps).round() as usize {
+			velocity = smooth_velocity(velocity, Vec2::zero(), dt);
+		}
+		velocity.x
+	}
+
+	#[test]
+	fn fling_speed_does_not_depend_on_the_frame_rate() {
+		for pause in [0.0, 0.1] {
+			let slow = fling_after(pause, 30.0);
+			let fast = fling_after(pause, 240.0);
+			assert!((slow - fast).abs() < 1e-6, "{slow} vs {fast}");
+		}
+		assert!(fling_after(0.0, 60.0) > 980.0);
+		// Letting go after a short pause doesn't throw the buddy.
+		assert!(fling_after(0.2, 60.0) < FLING_MIN_SPEED);
+	}
+
+	#[test]
+	fn bounces_off_monitor_edges() {
+		let (pos, velocity) = bounce_off_edges(
+			Vec2::new(-10.0, 50.0),
+			Vec2::new(-100.0, 20.0),
+			Vec2::zero(),
+			Vec2::new(200.0, 200.0),
+			0.5,
+		);
+		assert_eq!((pos.x, pos.y), (0.0, 50.0));
+		assert_eq!((velocity.x, velocity.y), (50.0, 20.0));
+	}
+
+	#[test]
+	fn window_bigger_than_monitor_does_not_panic() {
+		// A 2048px buddy is a 2662px window, on a 1920x1080 monitor.
+		let (pos, velocity) = bounce_off_edges(
+			Vec2::new(300.0, -40.0),
+			Vec2::new(100.0, -100.0),
+			Vec2::zero(),
+			Vec2::new(1920.0 - 2662.0, 1080.0 - 2662.0),
+			0.5,
+		);
+		assert_eq!((pos.x, pos.y), (0.0, 0.0));
+		assert_eq!((velocity.x, velocity.y), (-50.0, 50.0));
+	}
 }
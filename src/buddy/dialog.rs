@@ -0,0 +1,195 @@
+//! Dialog loaded from data files, so it can be edited and translated without rebuilding.
+//!
+//! Every buddy has a few base paths to look in (see [`BuddyDefinition::dialog_files`]).
+//! For each locale, most specific first, `{base}.{locale}.json` is tried in every base
+//! path, then `{base}.json`, and finally the buddy's built-in [`BuddyDefinition::dialog`].
+//! A file is laid out like the `dialog` section of a pack manifest, and only needs the
+//! kinds it wants to change.
+
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
+
+use super::{super::config, BuddyDefinition, DialogKind};
+
+type DialogFile = HashMap<DialogKind, Vec<Vec<String>>>;
+
+const DIALOG_DIR: &str = "dialog";
+
+/// Base paths for a built-in buddy: the user's config directory first, then the
+/// bundled assets.
+pub fn default_files(name: &str) -> Vec<PathBuf> {
+	let name = name.to_lowercase();
+	vec![
+		config::get_config_dir().join(DIALOG_DIR).join(&name),
+		config::get_assets_dir().join(DIALOG_DIR).join(&name),
+	]
+}
+
+/// Locales to look for, e.g. `de_DE` then `de` for `de_DE.UTF-8`. `configured` wins
+/// over the environment.
+pub fn locales(configured: Option<&str>) -> Vec<String> {
+	let locale = configured.map(str::to_string).or_else(|| {
+		["LC_ALL", "LC_MESSAGES", "LANG"]
+			.iter()
+			.filter_map(|var| std::env::var(var).ok())
+			.find(|value| !value.is_empty())
+	});
+	let Some(locale) = locale else {
+		return Vec::new();
+	};
+
+	let locale = locale.split(['.', '@']).next().unwrap_or_default();
+	if locale.is_empty() || locale == "C" || locale == "POSIX" {
+		return Vec::new();
+	}
+
+	let mut locales = vec![locale.to_string()];
+	if let Some((language, _)) = locale.split_once(['_', '-']) {
+		locales.push(language.to_string());
+	}
+	locales
+}
+
+/// Lines of `kind` from a dialog file built into the program, which built-in buddies
+/// fall back to when their bundled file is missing.
+pub fn builtin(json: &str, kind: DialogKind) -> Vec<Vec<String>> {
+	let mut file: DialogFile = serde_json::from_str(json).expect("invalid built-in dialog");
+	file.remove(&kind).unwrap_or_default()
+}
+
+/// Lines of `kind` for `buddy`, from the first dialog file that has any.
+pub fn lines(
+	buddy: &dyn BuddyDefinition,
+	kind: DialogKind,
+	locale: Option<&str>,
+) -> Vec<Vec<String>> {
+	let bases = buddy.dialog_files();
+	let mut paths = Vec::new();
+	for locale in locales(locale) {
+		paths.extend(
+			bases
+				.iter()
+				.map(|base| with_suffix(base, &format!(".{locale}.json"))),
+		);
+	}
+	paths.extend(bases.iter().map(|base| with_suffix(base, ".json")));
+
+	for path in paths {
+		if !path.exists() {
+			continue;
+		}
+		match read(&path) {
+			Ok(mut file) => {
+				if let Some(lines) = file.remove(&kind) {
+					return lines;
+				}
+			}
+			Err(err) => tracing::warn!("skipping dialog file: {err}"),
+		}
+	}
+	buddy.dialog(kind)
+}
+
+fn with_suffix(base: &Path, suffix: &str) -> PathBuf {
+	let mut path = base.as_os_str().to_owned();
+	path.push(suffix);
+	PathBuf::from(path)
+}
+
+fn read(path: &Path) -> Result<DialogFile, String> {
+	let contents = std::fs::read_to_string(path)
+		.map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+	serde_json::from_str(&contents)
+		.map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		super::{super::animation, buddies},
+		*,
+	};
+
+	/// A buddy whose only dialog files are the given bases.
+	struct Files(Vec<PathBuf>);
+
+	impl BuddyDefinition for Files {
+		fn name(&self) -> &str {
+			"files"
+		}
+
+		fn dialog(&self, _: DialogKind) -> Vec<Vec<String>> {
+			vec![vec!["built in".to_string()]]
+		}
+
+		fn dialog_files(&self) -> Vec<PathBuf> {
+			self.0.clone()
+		}
+
+		fn animation(&self) -> animation::Source {
+			animation::Source::Files {
+				paths: Vec::new(),
+				fps: 1.0,
+			}
+		}
+
+		fn font(&self) -> &str {
+			""
+		}
+	}
+
+	#[test]
+	fn locales_go_from_most_to_least_specific() {
+		assert_eq!(locales(Some("de_DE.UTF-8")), ["de_DE", "de"]);
+		assert_eq!(locales(Some("pt-BR")), ["pt-BR", "pt"]);
+		assert_eq!(locales(Some("fr")), ["fr"]);
+		assert_eq!(locales(Some("de_DE@euro")), ["de_DE", "de"]);
+		assert_eq!(locales(Some("sr_RS.UTF-8@latin")), ["sr_RS", "sr"]);
+		for untranslated in ["C", "POSIX", "C.UTF-8", ""] {
+			assert!(locales(Some(untranslated)).is_empty(), "{untranslated:?}");
+		}
+	}
+
+	#[test]
+	fn prefers_the_most_specific_locale_file() {
+		let dir = std::env::temp_dir().join(format!("funfriend-dialog-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		for (file, contents) in [
+			("a.json", r#"{ "chatter": [["a"]], "touched": [["a"]] }"#),
+			(
+				"a.de.json",
+				r#"{ "chatter": [["a de"]], "moved": [["a de"]] }"#,
+			),
+			("b.de_DE.json", r#"{ "chatter": [["b de_DE"]] }"#),
+			("a.fr.json", "not json"),
+		] {
+			std::fs::write(dir.join(file), contents).unwrap();
+		}
+		let buddy = Files(vec![dir.join("a"), dir.join("b")]);
+		let line = |kind, locale| lines(&buddy, kind, Some(locale))[0][0].clone();
+
+		// Every base is tried for a locale before moving on to a less specific one.
+		assert_eq!(line(DialogKind::Chatter, "de_DE.UTF-8"), "b de_DE");
+		assert_eq!(line(DialogKind::Moved, "de_DE.UTF-8"), "a de");
+		assert_eq!(line(DialogKind::Touched, "de_DE.UTF-8"), "a");
+		// Broken files are skipped.
+		assert_eq!(line(DialogKind::Chatter, "fr_FR"), "a");
+		assert_eq!(line(DialogKind::Chatter, "C"), "a");
+		assert_eq!(line(DialogKind::Moved, "C"), "built in");
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn built_in_buddies_have_lines_of_every_kind() {
+		let buddies: [&dyn BuddyDefinition; 3] =
+			[&buddies::Funfriend, &buddies::Catfriend, &buddies::God];
+		for buddy in buddies {
+			for kind in [DialogKind::Chatter, DialogKind::Moved, DialogKind::Touched] {
+				assert!(!buddy.dialog(kind).is_empty(), "{} {kind:?}", buddy.name());
+			}
+		}
+	}
+}
@@ -1,45 +1,184 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use serde::Deserialize;
 
 use super::{
+	animation,
+	audio::{self, Audio},
 	config,
-	texture::{SizedTexture, TextureBasket},
-	window::Windowed,
+	texture::{load_texture, SizedTexture, TextureBasket},
 };
 
+pub mod body;
 pub mod buddies;
 pub mod context;
+pub mod dialog;
+pub mod movement;
 pub mod renderer;
 
+pub use body::Body;
 pub use context::Context;
 pub use renderer::Renderer;
 
+/// Font to take chars from that a buddy's own font doesn't have.
+pub const FALLBACK_FONT: &str = "assets/fonts/SpaceMono";
+/// Pixel size TrueType/OpenType fonts are rasterized at. Bitmap fonts ignore it.
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
 pub trait BuddyDefinition {
 	fn name(&self) -> &str;
+	/// Built-in dialog, used when no dialog file has lines of `kind`.
 	fn dialog(&self, kind: DialogKind) -> Vec<Vec<String>>;
-	fn body(&self) -> TextureBasket;
-	fn background(&self) -> Option<SizedTexture> {
+	/// Base paths of dialog files that override [`Self::dialog`], see [`dialog`].
+	fn dialog_files(&self) -> Vec<PathBuf> {
+		dialog::default_files(self.name())
+	}
+	/// Where the body's animation frames and their timing come from. Played in
+	/// every state without an animation of its own.
+	fn animation(&self) -> animation::Source;
+	/// Animation for `state`, `None` to use [`Self::animation`].
+	fn state_animation(&self, _state: AnimationState) -> Option<animation::Source> {
 		None
 	}
-	fn play_talk_sound(&self) {}
+	/// Animations played once when switching states, before the new state's own.
+	fn transitions(&self) -> Vec<Transition> {
+		Vec::new()
+	}
+	fn body(&self) -> Body {
+		let default = TextureBasket::load(&self.animation()).expect("Failed to load animation.");
+		let mut body = Body::new(default);
+		for state in AnimationState::ALL {
+			let Some(source) = self.state_animation(state) else {
+				continue;
+			};
+			match TextureBasket::load(&source) {
+				Ok(basket) => body.set_animation(state, basket),
+				Err(err) => tracing::warn!("failed to load {state:?} animation: {err}"),
+			}
+		}
+		for transition in self.transitions() {
+			match TextureBasket::load(&transition.animation) {
+				Ok(basket) => body.add_transition(transition.from, transition.to, basket),
+				Err(err) => tracing::warn!(
+					"failed to load {:?} to {:?} transition: {err}",
+					transition.from,
+					transition.to
+				),
+			}
+		}
+		body
+	}
+	/// Image drawn behind the body, stretched to the whole window.
+	fn background_path(&self) -> Option<String> {
+		None
+	}
+	/// Fragment shader file drawing the body, `None` for the built-in bounce.
+	fn shader(&self) -> Option<String> {
+		None
+	}
+	/// Fragment shader file drawing the background.
+	fn background_shader(&self) -> Option<String> {
+		None
+	}
+	fn background(&self) -> Option<SizedTexture> {
+		let path = self.background_path()?;
+		match load_texture(&path, None) {
+			Ok(texture) => Some(texture),
+			Err(err) => {
+				tracing::warn!("failed to load {path}: {err}");
+				None
+			}
+		}
+	}
+	fn talk_sounds(&self) -> Vec<String> {
+		audio::default_talk_sounds()
+	}
+	fn play_talk_sound(&self, audio: &mut Audio) {
+		audio.play_random(&self.talk_sounds());
+	}
+	/// Either a BMFont path without the `.fnt` extension, or a `.ttf`/`.otf` file.
 	fn font(&self) -> &str;
+	fn font_size(&self) -> f32 {
+		DEFAULT_FONT_SIZE
+	}
+	/// Fonts to try, in order, for chars missing from [`Self::font`].
+	fn fallback_fonts(&self) -> Vec<String> {
+		vec![FALLBACK_FONT.to_string()]
+	}
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
 pub enum DialogKind {
 	Chatter,
 	Moved,
 	Touched,
 }
 
+/// What the buddy is doing, picked by [`Context`] to choose the body's animation.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationState {
+	Idle,
+	/// Speaking a line of dialog.
+	Talk,
+	/// Being dragged around.
+	Held,
+	/// Moving on its own or thrown.
+	Walk,
+	/// Left alone for a while, see `buddy.sleepAfter`.
+	Sleep,
+}
+
+impl AnimationState {
+	pub const ALL: [Self; 5] = [Self::Idle, Self::Talk, Self::Held, Self::Walk, Self::Sleep];
+
+	/// The state to animate when several apply, being held winning over talking,
+	/// talking over moving, and moving over sleeping.
+	pub fn pick(held: bool, speaking: bool, moving: bool, sleeping: bool) -> Self {
+		if held {
+			Self::Held
+		} else if speaking {
+			Self::Talk
+		} else if moving {
+			Self::Walk
+		} else if sleeping {
+			Self::Sleep
+		} else {
+			Self::Idle
+		}
+	}
+}
+
+/// An animation played once when switching from `from` (or any state when `None`)
+/// to `to`, like falling asleep.
+#[derive(Debug, Clone)]
+pub struct Transition {
+	pub from: Option<AnimationState>,
+	pub to: AnimationState,
+	pub animation: animation::Source,
+}
+
 pub fn make_context(
 	config: &config::Config,
 	buddy: Rc<RefCell<dyn BuddyDefinition>>,
-) -> Rc<RefCell<dyn Windowed>> {
-	Rc::new(RefCell::new(Context::new(config, buddy)))
+	audio: Rc<RefCell<Audio>>,
+	glfw: &mut glfw::Glfw,
+) -> Rc<RefCell<Context>> {
+	Rc::new(RefCell::new(Context::new(config, buddy, audio, glfw)))
 }
 
-pub fn make_buddy(r#type: config::BuddyType) -> Rc<RefCell<dyn BuddyDefinition>> {
+pub fn make_buddy(r#type: &config::BuddyType) -> Rc<RefCell<dyn BuddyDefinition>> {
 	match r#type {
 		config::BuddyType::Funfriend => Rc::new(RefCell::new(buddies::Funfriend)),
+		config::BuddyType::Catfriend => Rc::new(RefCell::new(buddies::Catfriend)),
+		config::BuddyType::God => Rc::new(RefCell::new(buddies::God)),
+		config::BuddyType::Pack(reference) => match buddies::PackBuddy::find(reference) {
+			Ok(pack) => Rc::new(RefCell::new(pack)),
+			Err(err) => {
+				tracing::warn!("failed to load buddy pack: {err}");
+				Rc::new(RefCell::new(buddies::Funfriend))
+			}
+		},
 	}
 }
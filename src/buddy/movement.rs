@@ -0,0 +1,376 @@
+use glfw::ffi::GLFWmonitor;
+use rand::Rng as _;
+
+use super::super::{config, ease, vec2::Vec2};
+
+const WANDER_TIMER: f64 = 4.0;
+const FOLLOW_DIST: f64 = 120.0;
+const GRAVITY: f64 = 2000.0;
+const WALK_DIST: f64 = 200.0;
+
+/// Snapshot of the buddy handed to a [`MovementBehavior`] every frame.
+#[derive(Debug, Clone, Copy)]
+pub struct BuddyState {
+	/// Current top-left position of the buddy window.
+	pub position: Vec2,
+	pub size: Vec2,
+	/// Where the buddy was last put down or sent to stay.
+	pub static_pos: Vec2,
+	/// Cursor position relative to the top-left of the buddy window.
+	pub cursor: Vec2,
+	pub speed: f64,
+	pub speaking: bool,
+	/// Whether the buddy was let go of less than a moment ago.
+	pub recently_held: bool,
+	/// Left alone long enough to doze off, so it shouldn't wander around.
+	pub sleeping: bool,
+	/// The monitor the buddy window overlaps the most, if any.
+	pub monitor: Option<Monitor>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Monitor {
+	pub position: Vec2,
+	pub size: Vec2,
+	pub work_area_position: Vec2,
+	pub work_area_size: Vec2,
+}
+
+impl Monitor {
+	pub fn from_ptr(monitor: *mut GLFWmonitor) -> Option<Self> {
+		if monitor.is_null() {
+			return None;
+		}
+
+		let (mut x, mut y) = (0, 0);
+		let (mut wx, mut wy, mut ww, mut wh) = (0, 0, 0, 0);
+		let mode = unsafe {
+			glfw::ffi::glfwGetMonitorPos(monitor, &mut x, &mut y);
+			glfw::ffi::glfwGetMonitorWorkarea(monitor, &mut wx, &mut wy, &mut ww, &mut wh);
+			glfw::ffi::glfwGetVideoMode(monitor).as_ref()?
+		};
+
+		Some(Self {
+			position: Vec2::new_i(x, y),
+			size: Vec2::new_i(mode.width, mode.height),
+			work_area_position: Vec2::new_i(wx, wy),
+			work_area_size: Vec2::new_i(ww, wh),
+		})
+	}
+
+	pub fn all() -> Vec<Self> {
+		let mut monitor_count = 0;
+		unsafe {
+			let monitors = glfw::ffi::glfwGetMonitors(&mut monitor_count);
+			(0..monitor_count as usize)
+				.filter_map(|i| Self::from_ptr(*monitors.add(i)))
+				.collect()
+		}
+	}
+}
+
+/// Where a [`MovementBehavior`] wants the buddy to go.
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+	/// Ease towards `position` over `duration` seconds.
+	Goto {
+		position: Vec2,
+		duration: f64,
+		set_as_static: bool,
+		easing: fn(f64) -> f64,
+	},
+	/// Jump straight to `position` this frame.
+	Set(Vec2),
+}
+
+impl Target {
+	pub fn goto(position: Vec2, duration: f64, set_as_static: bool) -> Self {
+		Self::Goto {
+			position,
+			duration,
+			set_as_static,
+			easing: ease::in_out_sine,
+		}
+	}
+}
+
+/// Decides where the buddy moves on its own. Only consulted while the buddy
+/// isn't being held, thrown or already easing towards a previous target.
+pub trait MovementBehavior {
+	fn update(&mut self, state: &BuddyState, monitors: &[Monitor], dt: f64) -> Option<Target>;
+}
+
+pub fn from_config(behavior: config::Behavior) -> Box<dyn MovementBehavior> {
+	match behavior {
+		config::Behavior::Normal => Box::new(Normal::default()),
+		config::Behavior::Dvd => Box::new(Dvd::new()),
+		config::Behavior::Gravity => Box::new(Gravity::default()),
+	}
+}
+
+/// Wanders around its resting spot, and follows the cursor while speaking.
+#[derive(Default)]
+pub struct Normal {
+	wander: Wander,
+	follow: Follow,
+	stay: Stay,
+}
+
+impl MovementBehavior for Normal {
+	fn update(&mut self, state: &BuddyState, monitors: &[Monitor], dt: f64) -> Option<Target> {
+		if state.recently_held {
+			self.stay.update(state, monitors, dt)
+		} else if state.speaking {
+			self.follow.update(state, monitors, dt)
+		} else if state.sleeping {
+			self.stay.update(state, monitors, dt)
+		} else {
+			self.wander.update(state, monitors, dt)
+		}
+	}
+}
+
+pub struct Wander {
+	timer: f64,
+}
+
+impl Default for Wander {
+	fn default() -> Self {
+		Self {
+			timer: WANDER_TIMER,
+		}
+	}
+}
+
+impl MovementBehavior for Wander {
+	fn update(&mut self, state: &BuddyState, _: &[Monitor], dt: f64) -> Option<Target> {
+		self.timer -= dt;
+		if self.timer > 0.0 {
+			return None;
+		}
+		self.timer = WANDER_TIMER;
+		Some(Target::goto(
+			state.static_pos + Vec2::rand(0.0..40.0),
+			4.0,
+			false,
+		))
+	}
+}
+
+#[derive(Default)]
+pub struct Follow;
+
+impl MovementBehavior for Follow {
+	fn update(&mut self, state: &BuddyState, _: &[Monitor], _: f64) -> Option<Target> {
+		let mut target = state.position;
+		if state.cursor.x.abs() > FOLLOW_DIST {
+			target.x += state.cursor.x - FOLLOW_DIST * state.cursor.x.signum();
+		}
+		if state.cursor.y.abs() > FOLLOW_DIST {
+			target.y += state.cursor.y - FOLLOW_DIST * state.cursor.y.signum();
+		}
+
+		Some(Target::goto(target, 1.0, true))
+	}
+}
+
+#[derive(Default)]
+pub struct Stay;
+
+impl MovementBehavior for Stay {
+	fn update(&mut self, _: &BuddyState, _: &[Monitor], _: f64) -> Option<Target> {
+		None
+	}
+}
+
+/// Bounces around the current monitor like an old DVD player screensaver.
+pub struct Dvd {
+	dir: Vec2,
+	pos: Option<Vec2>,
+}
+
+impl Dvd {
+	pub fn new() -> Self {
+		let mut rng = rand::thread_rng();
+		let x = rng.gen_range(-1.0..1.0);
+		let y = rng.gen_range(-1.0..1.0);
+		Self {
+			dir: Vec2::new(x, y).normalize(),
+			pos: None,
+		}
+	}
+}
+
+impl MovementBehavior for Dvd {
+	fn update(&mut self, state: &BuddyState, _: &[Monitor], dt: f64) -> Option<Target> {
+		let monitor = state.monitor?;
+
+		// window positions are whole pixels, so keep our own sub-pixel position
+		// and only resync when something else (like dragging) moved the window
+		let mut pos = match self.pos {
+			Some(pos) if pos.dist(state.position) < 2.0 => pos,
+			_ => state.position,
+		};
+		pos += self.dir * state.speed * dt;
+
+		let min = monitor.position;
+		let max = monitor.position + monitor.size - state.size;
+		if pos.x <= min.x {
+			tracing::info!("hit left wall");
+			self.dir.x = -self.dir.x;
+			pos.x = min.x;
+		}
+		if pos.y <= min.y {
+			tracing::info!("hit top wall");
+			self.dir.y = -self.dir.y;
+			pos.y = min.y;
+		}
+		if pos.x >= max.x {
+			tracing::info!("hit right wall");
+			self.dir.x = -self.dir.x;
+			pos.x = max.x;
+		}
+		if pos.y >= max.y {
+			tracing::info!("hit bottom wall");
+			self.dir.y = -self.dir.y;
+			pos.y = max.y;
+		}
+
+		self.pos = Some(pos);
+		Some(Target::Set(pos))
+	}
+}
+
+/// Falls to the bottom of the monitor work area and walks along it. GLFW can't
+/// list other windows (and Wayland doesn't let anyone), so the work area floor
+/// is the only thing to stand on.
+pub struct Gravity {
+	walk_timer: f64,
+}
+
+impl Default for Gravity {
+	fn default() -> Self {
+		Self {
+			walk_timer: WANDER_TIMER,
+		}
+	}
+}
+
+impl MovementBehavior for Gravity {
+	fn update(&mut self, state: &BuddyState, _: &[Monitor], dt: f64) -> Option<Target> {
+		let monitor = state.monitor?;
+		let left = monitor.work_area_position.x;
+		// A buddy wider than the work area can't walk anywhere but the left edge.
+		let right =
+			(monitor.work_area_position.x + monitor.work_area_size.x - state.size.x).max(left);
+		let floor = monitor.work_area_position.y + monitor.work_area_size.y - state.size.y;
+
+		let pos = state.position;
+		if (pos.y - floor).abs() >= 1.0 {
+			return Some(Target::Goto {
+				position: Vec2::new(pos.x, floor),
+				duration: (2.0 * (floor - pos.y).abs() / GRAVITY).sqrt(),
+				set_as_static: true,
+				easing: ease::in_sine,
+			});
+		}
+
+		if state.recently_held || state.sleeping {
+			return None;
+		}
+
+		self.walk_timer -= dt;
+		if self.walk_timer > 0.0 {
+			return None;
+		}
+		self.walk_timer = WANDER_TIMER;
+
+		let walk = rand::thread_rng().gen_range(-WALK_DIST..WALK_DIST);
+		let target_x = (pos.x + walk).clamp(left, right);
+		let duration = (target_x - pos.x).abs() / state.speed;
+		if duration <= 0.0 {
+			return None;
+		}
+		Some(Target::goto(Vec2::new(target_x, floor), duration, true))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn state(position: Vec2, size: Vec2, monitor: Monitor) -> BuddyState {
+		BuddyState {
+			position,
+			size,
+			static_pos: position,
+			cursor: Vec2::zero(),
+			speed: 50.0,
+			speaking: false,
+			recently_held: false,
+			sleeping: false,
+			monitor: Some(monitor),
+		}
+	}
+
+	fn monitor(size: Vec2) -> Monitor {
+		Monitor {
+			position: Vec2::zero(),
+			size,
+			work_area_position: Vec2::zero(),
+			work_area_size: size,
+		}
+	}
+
+	#[test]
+	fn gravity_falls_to_the_floor() {
+		let monitor = monitor(Vec2::new(800.0, 600.0));
+		let state = state(Vec2::new(100.0, 0.0), Vec2::new(100.0, 100.0), monitor);
+		match Gravity::default().update(&state, &[monitor], 0.0) {
+			Some(Target::Goto { position, .. }) => {
+				assert_eq!((position.x, position.y), (100.0, 500.0))
+			}
+			target => panic!("expected a fall, got {target:?}"),
+		}
+	}
+
+	#[test]
+	fn sleeping_only_stops_wandering() {
+		let monitor = monitor(Vec2::new(800.0, 600.0));
+		let mut state = state(Vec2::new(100.0, 0.0), Vec2::new(100.0, 100.0), monitor);
+		state.sleeping = true;
+
+		assert!(Normal::default()
+			.update(&state, &[monitor], WANDER_TIMER)
+			.is_none());
+		assert!(matches!(
+			Gravity::default().update(&state, &[monitor], WANDER_TIMER),
+			Some(Target::Goto { .. })
+		));
+		assert!(matches!(
+			Dvd::new().update(&state, &[monitor], 0.1),
+			Some(Target::Set(_))
+		));
+
+		// On the floor, a sleeping gravity buddy stays put instead of walking off.
+		state.position = Vec2::new(100.0, 500.0);
+		assert!(Gravity::default()
+			.update(&state, &[monitor], WANDER_TIMER)
+			.is_none());
+	}
+
+	#[test]
+	fn gravity_walks_with_a_buddy_wider_than_the_monitor() {
+		let monitor = monitor(Vec2::new(800.0, 600.0));
+		let size = Vec2::new(1000.0, 100.0);
+		let state = state(Vec2::new(300.0, 500.0), size, monitor);
+		let mut gravity = Gravity::default();
+		for _ in 0..10 {
+			if let Some(Target::Goto { position, .. }) =
+				gravity.update(&state, &[monitor], WANDER_TIMER)
+			{
+				assert_eq!(position.x, 0.0);
+			}
+		}
+	}
+}
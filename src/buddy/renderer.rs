@@ -1,26 +1,27 @@
 use std::cell::RefCell;
 use std::ffi::CString;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use gl::types::*;
-use glfw::Context;
 
 use super::{
 	super::{
 		config,
-		texture::{SizedTexture, TextureBasket},
+		graphics::{geometry, shader::ShaderProgram},
+		texture::SizedTexture,
 		vec2::Vec2,
-		Window,
+		Window, FUNFRIEND_FRAG, NOP_FRAG, NOP_VERT,
 	},
-	BuddyDefinition,
+	Body, BuddyDefinition,
 };
 
 pub struct Renderer {
-	pub body_shader: GLuint,
-	pub bg_shader: GLuint,
+	pub body_shader: ShaderProgram,
+	pub bg_shader: ShaderProgram,
 	pub vertex_array: GLuint,
 	pub vertex_buffer: GLuint,
-	pub body: TextureBasket,
+	pub body: Body,
 	pub background: Option<SizedTexture>,
 	pub resolution: Vec2,
 }
@@ -33,9 +34,8 @@ impl Renderer {
 	) -> Self {
 		let buddy = buddy.borrow();
 
-		window.handle.make_current();
-		gl::load_with(|s| window.glfw.get_proc_address_raw(s) as *const _);
-		let (buddy_shader, bg_shader) = Self::init_shaders();
+		window.make_current();
+		let (buddy_shader, bg_shader) = Self::init_shaders(config, &*buddy);
 		let (vertex_array, vertex_buffer) = Self::init_buffers();
 		let body = buddy.body();
 		let background = buddy.background();
@@ -56,14 +56,8 @@ impl Renderer {
 	}
 
 	fn init_buffers() -> (u32, u32) {
-		let vertices: [f32; 20] = [
-			1.0, 1.0, 0.0, 1.0, 1.0, // top right
-			1.0, -1.0, 0.0, 1.0, 0.0, // bottom right
-			-1.0, -1.0, 0.0, 0.0, 0.0, // bottom left
-			-1.0, 1.0, 0.0, 0.0, 1.0, // top left
-		];
-
-		let indices: [u32; 6] = [0, 1, 3, 1, 2, 3];
+		let vertices = geometry::QUAD_VERTICES;
+		let indices = geometry::QUAD_INDICES;
 
 		let mut vertex_array = 0;
 		let mut vertex_buffer = 0;
@@ -114,14 +108,19 @@ impl Renderer {
 		(vertex_array, vertex_buffer)
 	}
 
-	fn init_shaders() -> (GLuint, GLuint) {
-		let ff_frag = std::str::from_utf8(super::super::FUNFRIEND_FRAG).unwrap();
-		let nop_vert = std::str::from_utf8(super::super::NOP_VERT).unwrap();
-		let nop_frag = std::str::from_utf8(super::super::NOP_FRAG).unwrap();
-		let buddy_shader = super::super::glfn::shader(ff_frag, nop_vert);
-		let bg_shader = super::super::glfn::shader(nop_frag, nop_vert);
-
-		(buddy_shader, bg_shader)
+	/// Shader files from the config win over the buddy's own.
+	fn init_shaders(
+		config: &config::Config,
+		buddy: &dyn BuddyDefinition,
+	) -> (ShaderProgram, ShaderProgram) {
+		let body = (config.buddy.shader.clone()).or_else(|| buddy.shader().map(PathBuf::from));
+		let background = (config.buddy.background_shader.clone())
+			.or_else(|| buddy.background_shader().map(PathBuf::from));
+
+		(
+			ShaderProgram::new(body, FUNFRIEND_FRAG, NOP_VERT),
+			ShaderProgram::new(background, NOP_FRAG, NOP_VERT),
+		)
 	}
 
 	//noinspection RsCStringPointer
@@ -138,6 +137,8 @@ impl Renderer {
 			gl::Viewport(0, 0, window_width, window_height);
 		}
 
+		self.body_shader.poll(dt);
+		self.bg_shader.poll(dt);
 		self.body.update(dt);
 		let frame = self.body.texture();
 
@@ -149,11 +150,11 @@ impl Renderer {
 
 			if let Some(bg_texture) = &self.background {
 				gl::BindTexture(gl::TEXTURE_2D, bg_texture.tex);
-				gl::UseProgram(self.bg_shader);
+				gl::UseProgram(self.bg_shader.program);
 
 				gl::Uniform1i(
 					gl::GetUniformLocation(
-						self.bg_shader,
+						self.bg_shader.program,
 						CString::new("texture1").unwrap().as_ptr(),
 					),
 					0,
@@ -164,18 +165,18 @@ impl Renderer {
 			}
 
 			gl::BindTexture(gl::TEXTURE_2D, frame.tex);
-			gl::UseProgram(self.body_shader);
+			gl::UseProgram(self.body_shader.program);
 
 			gl::Uniform1i(
 				gl::GetUniformLocation(
-					self.body_shader,
+					self.body_shader.program,
 					CString::new("texture1").unwrap().as_ptr(),
 				),
 				0,
 			);
 			gl::Uniform2f(
 				gl::GetUniformLocation(
-					self.body_shader,
+					self.body_shader.program,
 					CString::new("funfriendSize").unwrap().as_ptr(),
 				),
 				self.funfriend_size().0 as f32,
@@ -183,15 +184,18 @@ impl Renderer {
 			);
 			gl::Uniform2f(
 				gl::GetUniformLocation(
-					self.body_shader,
+					self.body_shader.program,
 					CString::new("resolution").unwrap().as_ptr(),
 				),
 				window_width as f32,
 				window_height as f32,
 			);
 			gl::Uniform1f(
-				gl::GetUniformLocation(self.body_shader, CString::new("time").unwrap().as_ptr()),
-				window.glfw.get_time() as f32,
+				gl::GetUniformLocation(
+					self.body_shader.program,
+					CString::new("time").unwrap().as_ptr(),
+				),
+				window.handle.glfw.get_time() as f32,
 			);
 
 			gl::BindVertexArray(self.vertex_array);
@@ -203,7 +207,12 @@ impl Renderer {
 		unsafe {
 			gl::DeleteVertexArrays(1, &self.vertex_array);
 			gl::DeleteBuffers(1, &self.vertex_buffer);
-			gl::DeleteProgram(self.bg_shader);
+			if let Some(background) = &self.background {
+				gl::DeleteTextures(1, &background.tex);
+			}
 		}
+		self.body.clean_up();
+		self.body_shader.clean_up();
+		self.bg_shader.clean_up();
 	}
 }
@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use super::{buddy::buddies::PackBuddy, config, vec2::Vec2};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "A desktop buddy that hangs out on your screen")]
+pub struct Cli {
+	#[command(flatten)]
+	pub overrides: Overrides,
+	#[command(subcommand)]
+	pub command: Option<Command>,
+}
+
+/// Config values to use for this run only. `cfg.json` is left untouched. They can
+/// go before or after the subcommand.
+#[derive(clap::Args, Debug, Default)]
+pub struct Overrides {
+	/// Buddy to show: funfriend, catfriend, god, or the name or path of a buddy pack.
+	#[arg(long, global = true, value_parser = parse_buddy_type)]
+	pub buddy: Option<config::BuddyType>,
+	/// How the buddy moves on its own: normal, dvd or gravity.
+	#[arg(long, global = true, value_parser = parse_behavior)]
+	pub behavior: Option<config::Behavior>,
+	/// Movement speed in pixels per second.
+	#[arg(long, global = true)]
+	pub speed: Option<f64>,
+	/// Buddy size in pixels, as WIDTHxHEIGHT or a single number for a square.
+	#[arg(long, global = true, value_parser = parse_size)]
+	pub size: Option<Vec2>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+	/// Show the buddy (the default).
+	Run,
+	/// Print the path of the config file.
+	ConfigPath,
+	/// Check the config file for errors without starting the buddy.
+	CheckConfig,
+	/// Print the default config.
+	DefaultConfig,
+	/// Render the buddy to an animated .gif, .png (APNG) or .webp file without opening a window.
+	Record {
+		output: PathBuf,
+		/// Length of the recording in seconds.
+		#[arg(long, default_value_t = 3.0)]
+		duration: f64,
+		/// Frames per second. GIF timing is in hundredths of a second, so 25 or 50 loop most evenly.
+		#[arg(long, default_value_t = 25.0)]
+		fps: f64,
+	},
+	/// Render every buddy and a sample dialog without a GPU, into golden images in DIR.
+	Snapshot {
+		dir: PathBuf,
+		/// Compare against the images already in DIR instead of overwriting them.
+		#[arg(long)]
+		check: bool,
+		/// Where to write the images that don't match when checking.
+		#[arg(long, default_value = "target/snapshots")]
+		actual: PathBuf,
+	},
+	/// Make a running buddy say something.
+	Say {
+		text: String,
+		/// Index of the buddy to talk through.
+		#[arg(long, default_value_t = 0)]
+		to: usize,
+	},
+	/// Move a running buddy to a screen position.
+	Goto {
+		x: f64,
+		y: f64,
+		/// How long the move takes, in seconds.
+		#[arg(long, default_value_t = 1.0)]
+		duration: f64,
+		/// Index of the buddy to move.
+		#[arg(long, default_value_t = 0)]
+		to: usize,
+	},
+	/// Change how a running buddy moves on its own.
+	SetBehavior {
+		#[arg(value_parser = parse_behavior)]
+		behavior: config::Behavior,
+		/// Index of the buddy to change.
+		#[arg(long, default_value_t = 0)]
+		to: usize,
+	},
+	/// Add another buddy to a running instance.
+	Spawn {
+		#[arg(value_parser = parse_buddy_type)]
+		r#type: Option<config::BuddyType>,
+	},
+	/// Close a running instance.
+	Quit,
+}
+
+impl Overrides {
+	/// The override flags that were given, as written on the command line.
+	pub fn given(&self) -> Vec<&'static str> {
+		[
+			("--buddy", self.buddy.is_some()),
+			("--behavior", self.behavior.is_some()),
+			("--speed", self.speed.is_some()),
+			("--size", self.size.is_some()),
+		]
+		.into_iter()
+		.filter_map(|(flag, given)| given.then_some(flag))
+		.collect()
+	}
+
+	/// The config to switch to once `cfg.json` changed to `file`: the file with these
+	/// overrides on top, or `None` when that's the config already in use.
+	pub fn reload(
+		&self,
+		mut file: config::Config,
+		current: &config::Config,
+	) -> Option<config::Config> {
+		self.apply(&mut file);
+		(file != *current).then_some(file)
+	}
+
+	pub fn apply(&self, config: &mut config::Config) {
+		if let Some(r#type) = &self.buddy {
+			config.buddy.r#type = r#type.clone();
+		}
+		if let Some(behavior) = self.behavior {
+			config.buddy.behavior = behavior;
+		}
+		if let Some(speed) = self.speed {
+			config.buddy.speed = speed;
+		}
+		if let Some(size) = self.size {
+			config.window.size = size;
+		}
+	}
+}
+
+fn parse_buddy_type(s: &str) -> Result<config::BuddyType, String> {
+	Ok(match s.to_lowercase().as_str() {
+		"funfriend" => config::BuddyType::Funfriend,
+		"catfriend" => config::BuddyType::Catfriend,
+		"god" => config::BuddyType::God,
+		// Loaded here too so a typo is reported instead of quietly showing funfriend.
+		_ => PackBuddy::find(s)
+			.map(|_| config::BuddyType::Pack(s.to_string()))
+			.map_err(|e| format!("{e}, expected funfriend, catfriend, god or a buddy pack"))?,
+	})
+}
+
+fn parse_behavior(s: &str) -> Result<config::Behavior, String> {
+	match s.to_lowercase().as_str() {
+		"normal" => Ok(config::Behavior::Normal),
+		"dvd" => Ok(config::Behavior::Dvd),
+		"gravity" => Ok(config::Behavior::Gravity),
+		_ => Err(format!(
+			"unknown behavior {s:?}, expected normal, dvd or gravity"
+		)),
+	}
+}
+
+fn parse_size(s: &str) -> Result<Vec2, String> {
+	let parse = |n: &str| {
+		n.trim()
+			.parse::<f64>()
+			.ok()
+			.filter(|n| *n > 0.0)
+			.ok_or_else(|| format!("invalid size {s:?}, expected WIDTHxHEIGHT or a single number"))
+	};
+	match s.split_once(['x', 'X']) {
+		Some((w, h)) => Ok(Vec2::new(parse(w)?, parse(h)?)),
+		None => {
+			let n = parse(s)?;
+			Ok(Vec2::new(n, n))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_sizes() {
+		let size = |s| parse_size(s).map(|size| (size.x, size.y));
+		assert_eq!(size("128"), Ok((128.0, 128.0)));
+		assert_eq!(size("200x100"), Ok((200.0, 100.0)));
+		assert_eq!(size("64X 48.5"), Ok((64.0, 48.5)));
+		for invalid in ["", "0", "-5", "10x", "x10", "10x0", "big", "1x2x3"] {
+			assert!(size(invalid).is_err(), "{invalid:?} was accepted");
+		}
+	}
+
+	#[test]
+	fn parses_behaviors() {
+		assert_eq!(parse_behavior("normal"), Ok(config::Behavior::Normal));
+		assert_eq!(parse_behavior("DVD"), Ok(config::Behavior::Dvd));
+		assert_eq!(parse_behavior("Gravity"), Ok(config::Behavior::Gravity));
+		assert!(parse_behavior("fly").is_err());
+	}
+
+	#[test]
+	fn rejects_buddies_that_are_neither_built_in_nor_packs() {
+		assert_eq!(parse_buddy_type("God"), Ok(config::BuddyType::God));
+		let pack = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/packs/bstrd");
+		assert_eq!(
+			parse_buddy_type(pack),
+			Ok(config::BuddyType::Pack(pack.to_string()))
+		);
+		assert!(parse_buddy_type("catfirend").is_err());
+	}
+
+	#[test]
+	fn reloads_keep_the_overrides() {
+		let overrides = Overrides {
+			speed: Some(2.0),
+			..Default::default()
+		};
+		let mut current = config::Config::default();
+		overrides.apply(&mut current);
+
+		let mut file = config::Config::default();
+		file.buddy.speed = 80.0;
+		file.buddy.behavior = config::Behavior::Dvd;
+		let reloaded = overrides.reload(file.clone(), &current).unwrap();
+		assert_eq!(reloaded.buddy.speed, 2.0);
+		assert_eq!(reloaded.buddy.behavior, config::Behavior::Dvd);
+
+		// A change to an overridden value only isn't a change at all.
+		file.buddy.behavior = current.buddy.behavior;
+		assert!(overrides.reload(file, &current).is_none());
+	}
+
+	#[test]
+	fn overrides_go_before_or_after_the_subcommand() {
+		for args in [
+			&["funfriend", "--buddy", "god", "--speed", "2", "run"][..],
+			&["funfriend", "run", "--buddy", "god", "--speed", "2"],
+		] {
+			let cli = Cli::try_parse_from(args).unwrap();
+			assert!(matches!(cli.command, Some(Command::Run)));
+			assert_eq!(cli.overrides.buddy, Some(config::BuddyType::God));
+			assert_eq!(cli.overrides.speed, Some(2.0));
+		}
+	}
+}
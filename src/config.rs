@@ -1,96 +1,519 @@
-use std::path::PathBuf;
+use std::{
+	fmt,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use super::{vec2::Vec2, APP_NAME};
 
 const CONFIG_FILENAME: &str = "cfg.json";
+const BACKUP_FILENAME: &str = "cfg.json.bak";
+
+/// Bumped whenever the layout of `cfg.json` changes. Older files are migrated on load.
+pub const CONFIG_VERSION: u32 = 1;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` turns a version `n` config into a version `n + 1` one.
+const MIGRATIONS: [Migration; CONFIG_VERSION as usize] = [migrate_v0];
+
+const SPEED_RANGE: std::ops::RangeInclusive<f64> = 1.0..=5000.0;
+const WINDOW_SIZE_RANGE: std::ops::RangeInclusive<f64> = 8.0..=2048.0;
+const VOLUME_RANGE: std::ops::RangeInclusive<f64> = 0.0..=1.0;
+const FLING_FRICTION_RANGE: std::ops::RangeInclusive<f64> = 0.0..=100.0;
+const FLING_RESTITUTION_RANGE: std::ops::RangeInclusive<f64> = 0.0..=1.0;
+const REVEAL_SPEED_RANGE: std::ops::RangeInclusive<f64> = 0.0..=1000.0;
+const PUNCTUATION_PAUSE_RANGE: std::ops::RangeInclusive<f64> = 0.0..=5.0;
+const TALK_SOUND_INTERVAL_RANGE: std::ops::RangeInclusive<f64> = 1.0..=100.0;
+const SLEEP_AFTER_RANGE: std::ops::RangeInclusive<f64> = 0.0..=86400.0;
 
 impl Default for Config {
 	fn default() -> Self {
 		Self {
+			version: CONFIG_VERSION,
 			window: Window {
 				size: Vec2::new(75.0, 75.0),
 			},
-			sound: Sound { master_volume: 1.0 },
+			sound: Sound {
+				master_volume: 1.0,
+				output: SoundOutput::Device,
+			},
 			buddy: Buddy {
 				r#type: BuddyType::Funfriend,
 				behavior: Behavior::Normal,
 				speed: 50.0,
+				fling_friction: default_fling_friction(),
+				fling_restitution: default_fling_restitution(),
+				sleep_after: default_sleep_after(),
+				shader: None,
+				background_shader: None,
 			},
+			dialog: Dialog::default(),
 		}
 	}
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl Default for Dialog {
+	fn default() -> Self {
+		Self {
+			reveal_speed: 30.0,
+			punctuation_pause: 0.25,
+			talk_sound_interval: 4,
+			locale: None,
+			shader: None,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
+	pub version: u32,
 	pub window: Window,
 	pub sound: Sound,
 	pub buddy: Buddy,
+	#[serde(default)]
+	pub dialog: Dialog,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Window {
 	pub size: Vec2,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Sound {
 	pub master_volume: f32,
+	#[serde(default)]
+	pub output: SoundOutput,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SoundOutput {
+	#[default]
+	Device,
+	Null,
+	/// Writes everything that would have been played to a WAV file.
+	File(PathBuf),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Buddy {
 	pub r#type: BuddyType,
 	pub behavior: Behavior,
 	pub speed: f64,
+	/// How quickly a thrown buddy slows down, as an exponential decay rate per second.
+	#[serde(default = "default_fling_friction")]
+	pub fling_friction: f64,
+	/// Fraction of speed kept when a thrown buddy bounces off a monitor edge.
+	#[serde(default = "default_fling_restitution")]
+	pub fling_restitution: f64,
+	/// Seconds without being touched or speaking before the buddy falls asleep, 0 to stay awake.
+	#[serde(default = "default_sleep_after")]
+	pub sleep_after: f64,
+	/// Fragment shader file drawing the body, instead of the buddy's own or the built-in one.
+	#[serde(default)]
+	pub shader: Option<PathBuf>,
+	/// Fragment shader file drawing the background image.
+	#[serde(default)]
+	pub background_shader: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Dialog {
+	/// Characters revealed per second in speech bubbles, 0 shows the whole text at once.
+	pub reveal_speed: f64,
+	/// Extra seconds to wait after revealing punctuation.
+	pub punctuation_pause: f64,
+	/// A talk sound plays every this many revealed characters.
+	pub talk_sound_interval: u32,
+	/// Language of dialog files to prefer, like `de` or `pt_BR`. Taken from `LANG` if unset.
+	pub locale: Option<String>,
+	/// Fragment shader file drawing speech bubble text.
+	pub shader: Option<PathBuf>,
+}
+
+fn default_fling_friction() -> f64 {
+	3.0
+}
+
+fn default_fling_restitution() -> f64 {
+	0.6
+}
+
+fn default_sleep_after() -> f64 {
+	60.0
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum BuddyType {
 	Funfriend,
+	Catfriend,
+	God,
+	/// A data-driven buddy pack, referenced by directory path or pack name.
+	Pack(String),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Behavior {
 	Normal,
 	Dvd,
+	/// Falls to the bottom of the monitor work area and walks along it. Other
+	/// windows aren't known, so it never lands on them.
+	Gravity,
 }
 
-pub fn read() -> Config {
-	match std::fs::read_to_string(get_config_dir().join(CONFIG_FILENAME)) {
-		Ok(contents) => match serde_json::from_str(&contents) {
-			Ok(config) => config,
-			Err(err) => {
-				tracing::warn!("failed to parse config file: {err}");
-				Config::default()
+impl Config {
+	/// Checks that every value is in a sensible range, reporting all offending fields at once.
+	pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+		let mut errors = Vec::new();
+		let mut check = |field: &str, value: f64, range: std::ops::RangeInclusive<f64>| {
+			if !range.contains(&value) {
+				errors.push(FieldError {
+					field: field.to_string(),
+					message: format!(
+						"must be between {} and {}, got {value}",
+						range.start(),
+						range.end()
+					),
+				});
+			}
+		};
+
+		check("window.size.x", self.window.size.x, WINDOW_SIZE_RANGE);
+		check("window.size.y", self.window.size.y, WINDOW_SIZE_RANGE);
+		check(
+			"sound.masterVolume",
+			self.sound.master_volume as f64,
+			VOLUME_RANGE,
+		);
+		check("buddy.speed", self.buddy.speed, SPEED_RANGE);
+		check(
+			"buddy.flingFriction",
+			self.buddy.fling_friction,
+			FLING_FRICTION_RANGE,
+		);
+		check(
+			"buddy.flingRestitution",
+			self.buddy.fling_restitution,
+			FLING_RESTITUTION_RANGE,
+		);
+		check(
+			"buddy.sleepAfter",
+			self.buddy.sleep_after,
+			SLEEP_AFTER_RANGE,
+		);
+		check(
+			"dialog.revealSpeed",
+			self.dialog.reveal_speed,
+			REVEAL_SPEED_RANGE,
+		);
+		check(
+			"dialog.punctuationPause",
+			self.dialog.punctuation_pause,
+			PUNCTUATION_PAUSE_RANGE,
+		);
+		check(
+			"dialog.talkSoundInterval",
+			self.dialog.talk_sound_interval as f64,
+			TALK_SOUND_INTERVAL_RANGE,
+		);
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(errors)
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldError {
+	/// Dotted path to the field as written in `cfg.json`, e.g. `buddy.speed`.
+	pub field: String,
+	pub message: String,
+}
+
+impl fmt::Display for FieldError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.field, self.message)
+	}
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+	Missing,
+	Io(std::io::Error),
+	/// Not valid JSON at all.
+	Syntax(serde_json::Error),
+	Migration(String),
+	/// Valid JSON, but not a valid config, or out of range values.
+	Invalid(Vec<FieldError>),
+}
+
+impl fmt::Display for ReadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Missing => write!(f, "{} does not exist", get_config_path().display()),
+			Self::Io(err) => write!(f, "failed to read {}: {err}", get_config_path().display()),
+			Self::Syntax(err) => write!(
+				f,
+				"{} is not valid JSON: {err}",
+				get_config_path().display()
+			),
+			Self::Migration(err) => write!(
+				f,
+				"failed to migrate {}: {err}",
+				get_config_path().display()
+			),
+			Self::Invalid(errors) => {
+				write!(f, "{} has invalid settings:", get_config_path().display())?;
+				for error in errors {
+					write!(f, "\n  {error}")?;
+				}
+				Ok(())
 			}
-		},
+		}
+	}
+}
+
+pub struct Loaded {
+	pub config: Config,
+	/// The version the file was migrated from, if it wasn't current.
+	pub migrated_from: Option<u32>,
+}
+
+pub fn read() -> Result<Loaded, ReadError> {
+	read_from(&get_config_path())
+}
+
+fn read_from(path: &Path) -> Result<Loaded, ReadError> {
+	let contents = match std::fs::read_to_string(path) {
+		Ok(contents) => contents,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(ReadError::Missing),
+		Err(err) => return Err(ReadError::Io(err)),
+	};
+	parse(&contents)
+}
+
+pub fn parse(contents: &str) -> Result<Loaded, ReadError> {
+	let mut value: Value = serde_json::from_str(contents).map_err(ReadError::Syntax)?;
+	let version = migrate(&mut value).map_err(ReadError::Migration)?;
+
+	let config: Config = serde_path_to_error::deserialize(value).map_err(|err| {
+		ReadError::Invalid(vec![FieldError {
+			field: err.path().to_string(),
+			message: err.into_inner().to_string(),
+		}])
+	})?;
+	config.validate().map_err(ReadError::Invalid)?;
+
+	Ok(Loaded {
+		config,
+		migrated_from: (version != CONFIG_VERSION).then_some(version),
+	})
+}
+
+/// Reads the config file, writing the defaults if there is none and rewriting it
+/// if it had to be migrated. A broken file is reported and left alone so it can be
+/// fixed by hand, and the defaults are used instead.
+pub fn load() -> Config {
+	match read() {
+		Ok(Loaded {
+			config,
+			migrated_from: Some(version),
+		}) => {
+			tracing::info!("migrated config from version {version} to {CONFIG_VERSION}");
+			write(&config);
+			config
+		}
+		Ok(Loaded { config, .. }) => config,
+		Err(ReadError::Missing) => {
+			tracing::info!("no config file found, writing the defaults");
+			let config = Config::default();
+			write(&config);
+			config
+		}
 		Err(err) => {
-			tracing::warn!("failed to read config file: {err}");
+			tracing::error!("{err}");
+			tracing::warn!("using the default config until the file is fixed");
 			Config::default()
 		}
 	}
 }
 
+/// Brings an older config up to [`CONFIG_VERSION`] in place, returning the version it had.
+fn migrate(value: &mut Value) -> Result<u32, String> {
+	let object = value.as_object().ok_or("expected a JSON object")?;
+	let version = match object.get("version") {
+		Some(version) => version
+			.as_u64()
+			.and_then(|version| u32::try_from(version).ok())
+			.ok_or_else(|| format!("version must be a whole number, got {version}"))?,
+		// Before versioning, settings lived under `*_settings` keys.
+		None if object.contains_key("buddy_settings") => 0,
+		None => 1,
+	};
+	if version > CONFIG_VERSION {
+		return Err(format!(
+			"version {version} is newer than this build understands ({CONFIG_VERSION})"
+		));
+	}
+
+	for migration in &MIGRATIONS[version as usize..] {
+		migration(value)?;
+	}
+	value["version"] = CONFIG_VERSION.into();
+	Ok(version)
+}
+
+/// `{"buddy_settings": {"buddy_type": ...}}` and friends to `{"buddy": {"type": ...}}`.
+fn migrate_v0(value: &mut Value) -> Result<(), String> {
+	fn camel_case(key: &str) -> String {
+		let mut parts = key.split('_');
+		let mut result = parts.next().unwrap_or_default().to_string();
+		for part in parts {
+			let mut chars = part.chars();
+			if let Some(first) = chars.next() {
+				result.extend(first.to_uppercase());
+				result.push_str(chars.as_str());
+			}
+		}
+		result
+	}
+
+	let object = value.as_object_mut().ok_or("expected a JSON object")?;
+	for section in ["window", "sound", "buddy"] {
+		let Some(Value::Object(old)) = object.remove(&format!("{section}_settings")) else {
+			continue;
+		};
+		let new = old
+			.into_iter()
+			.map(|(key, value)| match key.as_str() {
+				"buddy_type" => ("type".to_string(), value),
+				_ => (camel_case(&key), value),
+			})
+			.collect();
+		object.insert(section.to_string(), Value::Object(new));
+	}
+	if let Some(Value::String(r#type)) = object.get_mut("buddy").and_then(|b| b.get_mut("type")) {
+		*r#type = r#type.to_lowercase();
+	}
+	Ok(())
+}
+
+/// Notices when the config file changes on disk by polling its modification time.
+pub struct Watcher {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+	timer: f64,
+}
+
+impl Watcher {
+	const POLL_INTERVAL: f64 = 1.0;
+
+	pub fn new() -> Self {
+		Self::watch(get_config_path())
+	}
+
+	fn watch(path: PathBuf) -> Self {
+		Self {
+			modified: Self::modified(&path),
+			path,
+			timer: Self::POLL_INTERVAL,
+		}
+	}
+
+	fn modified(path: &Path) -> Option<SystemTime> {
+		std::fs::metadata(path)
+			.and_then(|metadata| metadata.modified())
+			.ok()
+	}
+
+	/// Returns the new config once the file has changed and reads cleanly.
+	/// Broken edits are logged and skipped until the file changes again.
+	pub fn poll(&mut self, dt: f64) -> Option<Config> {
+		self.timer -= dt;
+		if self.timer > 0.0 {
+			return None;
+		}
+		self.timer = Self::POLL_INTERVAL;
+
+		let modified = Self::modified(&self.path);
+		if modified == self.modified {
+			return None;
+		}
+		self.modified = modified;
+
+		match read_from(&self.path) {
+			Ok(Loaded { config, .. }) => Some(config),
+			Err(ReadError::Missing) => None,
+			Err(err) => {
+				tracing::error!("{err}");
+				None
+			}
+		}
+	}
+}
+
+/// Writes the config, keeping a copy of the previous file next to it.
 pub fn write(config: &Config) {
+	if let Err(err) = write_in(&get_config_dir(), config) {
+		tracing::error!("{err}");
+	}
+}
+
+/// Writes the config into `config_dir`. The previous file is only backed up when
+/// the contents change, so writing the same config twice can't replace the backup
+/// with a copy of itself, and the new file is moved into place whole.
+fn write_in(config_dir: &Path, config: &Config) -> Result<(), String> {
 	let json = serde_json::to_string_pretty(config).expect("failed to serialize config");
+	std::fs::create_dir_all(config_dir)
+		.map_err(|err| format!("failed to create {}: {err}", config_dir.display()))?;
 
-	let config_dir = get_config_dir();
-	if !config_dir.exists() {
-		std::fs::create_dir(config_dir.clone()).expect("failed to create config dir");
+	let path = config_dir.join(CONFIG_FILENAME);
+	match std::fs::read_to_string(&path) {
+		Ok(old) if old == json => return Ok(()),
+		Ok(_) => {
+			std::fs::copy(&path, config_dir.join(BACKUP_FILENAME)).map_err(|err| {
+				format!("failed to back up config file, not overwriting it: {err}")
+			})?;
+		}
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+		Err(err) => return Err(format!("failed to read {}: {err}", path.display())),
 	}
 
-	std::fs::write(config_dir.join(CONFIG_FILENAME), json).expect("failed to write config file");
+	let temp = config_dir.join(format!("{CONFIG_FILENAME}.tmp"));
+	std::fs::write(&temp, json)
+		.and_then(|()| std::fs::rename(&temp, &path))
+		.map_err(|err| format!("failed to write {}: {err}", path.display()))
+}
+
+pub fn get_config_path() -> PathBuf {
+	get_config_dir().join(CONFIG_FILENAME)
 }
 
-fn get_config_dir() -> std::path::PathBuf {
+/// The bundled `assets` directory: next to the executable when it's been installed
+/// with one, or in the working directory, as when started with `cargo run`.
+pub fn get_assets_dir() -> PathBuf {
+	std::env::current_exe()
+		.ok()
+		.and_then(|exe| Some(exe.parent()?.join("assets")))
+		.filter(|dir| dir.is_dir())
+		.unwrap_or_else(|| PathBuf::from("assets"))
+}
+
+pub fn get_config_dir() -> std::path::PathBuf {
 	if cfg!(windows) {
 		PathBuf::from(std::env::var("APPDATA").expect("APPDATA env variable undefined"))
 			.join(APP_NAME)
@@ -106,3 +529,138 @@ fn get_config_dir() -> std::path::PathBuf {
 			.join(APP_NAME)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A config from before versioning, with every section under `*_settings`.
+	const V0_CONFIG: &str = r#"{
+		"window_settings": { "size": { "x": 100.0, "y": 120.0 } },
+		"sound_settings": { "master_volume": 0.5 },
+		"buddy_settings": { "buddy_type": "Catfriend", "behavior": "dvd", "speed": 80.0 }
+	}"#;
+
+	#[test]
+	fn migrates_v0_configs() {
+		let loaded = parse(V0_CONFIG).unwrap();
+		assert_eq!(loaded.migrated_from, Some(0));
+		let config = loaded.config;
+		assert_eq!(config.version, CONFIG_VERSION);
+		assert_eq!((config.window.size.x, config.window.size.y), (100.0, 120.0));
+		assert_eq!(config.sound.master_volume, 0.5);
+		assert_eq!(config.buddy.r#type, BuddyType::Catfriend);
+		assert_eq!(config.buddy.behavior, Behavior::Dvd);
+		assert_eq!(config.buddy.speed, 80.0);
+		assert_eq!(config.dialog, Dialog::default());
+
+		// Written back, it reads as the current version without changes.
+		let json = serde_json::to_string_pretty(&config).unwrap();
+		let reread = parse(&json).unwrap();
+		assert_eq!(reread.migrated_from, None);
+		assert_eq!(reread.config, config);
+	}
+
+	#[test]
+	fn rejects_versions_it_cant_migrate() {
+		for (version, message) in [
+			("2", "newer than this build"),
+			("4294967296", "whole number"),
+			("-1", "whole number"),
+			("\"1\"", "whole number"),
+		] {
+			let json = V0_CONFIG.replacen('{', &format!("{{ \"version\": {version},"), 1);
+			match parse(&json) {
+				Err(ReadError::Migration(err)) => assert!(err.contains(message), "{err}"),
+				other => panic!("version {version}: {:?}", other.map(|loaded| loaded.config)),
+			}
+		}
+	}
+
+	#[test]
+	fn reports_every_out_of_range_field() {
+		let mut config = Config::default();
+		config.buddy.speed = 0.0;
+		config.sound.master_volume = 2.0;
+		config.dialog.talk_sound_interval = 0;
+		let fields: Vec<String> = config
+			.validate()
+			.unwrap_err()
+			.into_iter()
+			.map(|error| error.field)
+			.collect();
+		assert_eq!(
+			fields,
+			[
+				"sound.masterVolume",
+				"buddy.speed",
+				"dialog.talkSoundInterval"
+			]
+		);
+		assert!(Config::default().validate().is_ok());
+	}
+
+	#[test]
+	fn backs_up_the_previous_file_only_when_it_changes() {
+		let dir = std::env::temp_dir().join(format!("funfriend-config-{}", std::process::id()));
+		let backup = dir.join(BACKUP_FILENAME);
+		let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+
+		let first = Config::default();
+		write_in(&dir, &first).unwrap();
+		assert!(!backup.exists());
+
+		let mut second = first.clone();
+		second.buddy.speed = 100.0;
+		write_in(&dir, &second).unwrap();
+		assert_eq!(parse(&read(&backup)).unwrap().config, first);
+		assert_eq!(
+			parse(&read(&dir.join(CONFIG_FILENAME))).unwrap().config,
+			second
+		);
+
+		// Writing the same config again keeps the older backup.
+		write_in(&dir, &second).unwrap();
+		assert_eq!(parse(&read(&backup)).unwrap().config, first);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn watcher_skips_edits_that_dont_read_cleanly() {
+		let dir = std::env::temp_dir().join(format!("funfriend-watch-{}", std::process::id()));
+		let path = dir.join(CONFIG_FILENAME);
+		write_in(&dir, &Config::default()).unwrap();
+		let mut watcher = Watcher::watch(path.clone());
+		let mut poll = || watcher.poll(Watcher::POLL_INTERVAL);
+
+		// Sets the modification time by hand, as quick edits can share one.
+		let edit = |contents: &str, second: u64| {
+			std::fs::write(&path, contents).unwrap();
+			std::fs::File::options()
+				.write(true)
+				.open(&path)
+				.unwrap()
+				.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(second))
+				.unwrap();
+		};
+		let with_speed = |speed: f64| {
+			let mut config = Config::default();
+			config.buddy.speed = speed;
+			serde_json::to_string(&config).unwrap()
+		};
+
+		assert!(poll().is_none());
+		edit(&with_speed(80.0), 1);
+		assert_eq!(poll().map(|config| config.buddy.speed), Some(80.0));
+		assert!(poll().is_none());
+
+		edit("{ \"version\": ", 2);
+		assert!(poll().is_none());
+		edit(&with_speed(-5.0), 3);
+		assert!(poll().is_none());
+
+		edit(&with_speed(90.0), 4);
+		assert_eq!(poll().map(|config| config.buddy.speed), Some(90.0));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,411 @@
+//! Local control socket for scripting a running buddy.
+//!
+//! Clients connect to a Unix domain socket and send one JSON request per line,
+//! e.g. `{"command": "say", "text": "BUILD FINISHED"}`. Every request gets a
+//! single JSON line back, either `{"ok": true}` or `{"ok": false, "error": "..."}`.
+
+use std::{
+	io::{BufRead as _, BufReader, ErrorKind, Read as _, Write as _},
+	os::unix::{
+		fs::{DirBuilderExt as _, MetadataExt as _, PermissionsExt as _},
+		net::{UnixListener, UnixStream},
+	},
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{config, APP_NAME};
+
+const SOCKET_FILENAME: &str = "control.sock";
+/// Longest request line a client can send before it's disconnected.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Request {
+	/// Index of the buddy the command is for, in the order they were spawned.
+	#[serde(default)]
+	pub buddy: usize,
+	#[serde(flatten)]
+	pub command: Command,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+	Say {
+		text: String,
+	},
+	Goto {
+		x: f64,
+		y: f64,
+		#[serde(default = "default_goto_duration")]
+		duration: f64,
+	},
+	SetBehavior {
+		behavior: config::Behavior,
+	},
+	SpawnBuddy {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		r#type: Option<config::BuddyType>,
+	},
+	Quit,
+}
+
+fn default_goto_duration() -> f64 {
+	1.0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Response {
+	pub ok: bool,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	/// Index of the newly spawned buddy, for `spawn_buddy`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub buddy: Option<usize>,
+}
+
+impl Response {
+	pub fn ok() -> Self {
+		Self {
+			ok: true,
+			..Default::default()
+		}
+	}
+
+	pub fn error(error: impl Into<String>) -> Self {
+		Self {
+			ok: false,
+			error: Some(error.into()),
+			..Default::default()
+		}
+	}
+}
+
+/// Where the control socket lives: `$XDG_RUNTIME_DIR/funfriend-rust/control.sock`,
+/// or `funfriend-rust-<uid>` in the temp directory if `XDG_RUNTIME_DIR` isn't set,
+/// so users sharing a temp directory each get their own.
+pub fn socket_path() -> PathBuf {
+	let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+		Some(runtime_dir) => PathBuf::from(runtime_dir).join(APP_NAME),
+		None => std::env::temp_dir().join(format!("{APP_NAME}-{}", current_uid())),
+	};
+	dir.join(SOCKET_FILENAME)
+}
+
+fn current_uid() -> u32 {
+	// SAFETY: getuid has no preconditions and can't fail.
+	unsafe { libc::getuid() }
+}
+
+/// Creates the socket's directory readable by its owner only, and makes sure an
+/// existing one belongs to this user, so nobody else can reach the socket.
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+	match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+		Ok(()) => return Ok(()),
+		Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+		Err(err) => return Err(format!("failed to create {}: {err}", dir.display())),
+	}
+
+	let metadata = std::fs::symlink_metadata(dir)
+		.map_err(|err| format!("failed to inspect {}: {err}", dir.display()))?;
+	if !metadata.is_dir() || metadata.uid() != current_uid() {
+		return Err(format!(
+			"{} is not a directory owned by the current user",
+			dir.display()
+		));
+	}
+	if metadata.mode() & 0o077 != 0 {
+		std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+			.map_err(|err| format!("failed to restrict {}: {err}", dir.display()))?;
+	}
+	Ok(())
+}
+
+/// Sends a single request to an already running instance and waits for its response.
+pub fn send(request: &Request) -> Result<Response, String> {
+	send_to(&socket_path(), request)
+}
+
+fn send_to(path: &Path, request: &Request) -> Result<Response, String> {
+	let mut stream = UnixStream::connect(path).map_err(|err| {
+		format!(
+			"failed to connect to {} (is funfriend running?): {err}",
+			path.display()
+		)
+	})?;
+
+	let mut json = serde_json::to_string(request).expect("failed to serialize request");
+	json.push('\n');
+	stream
+		.write_all(json.as_bytes())
+		.map_err(|err| format!("failed to send request: {err}"))?;
+
+	let mut line = String::new();
+	BufReader::new(stream)
+		.read_line(&mut line)
+		.map_err(|err| format!("failed to read response: {err}"))?;
+	serde_json::from_str(&line).map_err(|err| format!("invalid response {line:?}: {err}"))
+}
+
+struct Client {
+	stream: UnixStream,
+	buffer: Vec<u8>,
+	/// Responses not yet written, for clients slower to read than we are to answer.
+	output: Vec<u8>,
+}
+
+pub struct ControlServer {
+	listener: UnixListener,
+	clients: Vec<Client>,
+	path: PathBuf,
+}
+
+impl ControlServer {
+	pub fn bind() -> Result<Self, String> {
+		Self::bind_at(socket_path())
+	}
+
+	fn bind_at(path: PathBuf) -> Result<Self, String> {
+		if let Some(dir) = path.parent() {
+			create_private_dir(dir)?;
+		}
+
+		// A socket file that nobody answers on is left over from a crashed run.
+		if path.exists() {
+			if UnixStream::connect(&path).is_ok() {
+				return Err(format!(
+					"another instance is already listening on {}",
+					path.display()
+				));
+			}
+			let _ = std::fs::remove_file(&path);
+		}
+
+		let listener = UnixListener::bind(&path)
+			.map_err(|err| format!("failed to bind {}: {err}", path.display()))?;
+		listener
+			.set_nonblocking(true)
+			.map_err(|err| format!("failed to make control socket non-blocking: {err}"))?;
+		tracing::info!("listening for commands on {}", path.display());
+
+		Ok(Self {
+			listener,
+			clients: Vec::new(),
+			path,
+		})
+	}
+
+	/// Accepts new clients and hands every complete request line to `handle`,
+	/// writing its response back to the client. Never blocks.
+	pub fn poll(&mut self, mut handle: impl FnMut(Request) -> Response) {
+		loop {
+			match self.listener.accept() {
+				Ok((stream, _)) => {
+					if let Err(err) = stream.set_nonblocking(true) {
+						tracing::warn!("failed to make control client non-blocking: {err}");
+						continue;
+					}
+					self.clients.push(Client {
+						stream,
+						buffer: Vec::new(),
+						output: Vec::new(),
+					});
+				}
+				Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+				Err(err) => {
+					tracing::warn!("failed to accept control client: {err}");
+					break;
+				}
+			}
+		}
+
+		self.clients.retain_mut(|client| {
+			let open = client.read_available();
+			while let Some(line) = client.next_line() {
+				let response = match serde_json::from_str::<Request>(&line) {
+					Ok(request) => {
+						tracing::info!("control request: {request:?}");
+						handle(request)
+					}
+					Err(err) => Response::error(format!("invalid request: {err}")),
+				};
+				client.respond(&response);
+			}
+			if client.buffer.len() > MAX_LINE_LENGTH {
+				tracing::warn!(
+					"dropping control client sending lines over {MAX_LINE_LENGTH} bytes"
+				);
+				client.respond(&Response::error("request too long"));
+				let _ = client.flush();
+				return false;
+			}
+			if let Err(err) = client.flush() {
+				tracing::warn!("failed to answer control client: {err}");
+				return false;
+			}
+			open
+		});
+	}
+}
+
+impl Drop for ControlServer {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.path);
+	}
+}
+
+impl Client {
+	/// Reads whatever the client has sent so far. Returns false once the client hung up.
+	fn read_available(&mut self) -> bool {
+		let mut chunk = [0u8; 1024];
+		loop {
+			match self.stream.read(&mut chunk) {
+				Ok(0) => return false,
+				Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+				Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+				Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+				Err(err) => {
+					tracing::warn!("failed to read from control client: {err}");
+					return false;
+				}
+			}
+		}
+	}
+
+	fn next_line(&mut self) -> Option<String> {
+		let end = self.buffer.iter().position(|&b| b == b'\n')?;
+		let line: Vec<u8> = self.buffer.drain(..=end).collect();
+		let line = String::from_utf8_lossy(&line).trim().to_string();
+		if line.is_empty() {
+			self.next_line()
+		} else {
+			Some(line)
+		}
+	}
+
+	fn respond(&mut self, response: &Response) {
+		let json = serde_json::to_string(response).expect("failed to serialize response");
+		self.output.extend_from_slice(json.as_bytes());
+		self.output.push(b'\n');
+	}
+
+	/// Writes as much of the pending responses as the client takes without blocking.
+	fn flush(&mut self) -> std::io::Result<()> {
+		while !self.output.is_empty() {
+			match self.stream.write(&self.output) {
+				Ok(0) => return Err(ErrorKind::WriteZero.into()),
+				Ok(n) => {
+					self.output.drain(..n);
+				}
+				Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+				Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+				Err(err) => return Err(err),
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trips_commands_through_the_socket() {
+		let dir = std::env::temp_dir().join(format!("funfriend-control-{}", std::process::id()));
+		let path = dir.join(SOCKET_FILENAME);
+		let mut server = ControlServer::bind_at(path.clone()).unwrap();
+
+		let requests = [
+			Command::Say {
+				text: "BUILD FINISHED".to_string(),
+			},
+			Command::SetBehavior {
+				behavior: config::Behavior::Gravity,
+			},
+			Command::Quit,
+		];
+		let client = std::thread::spawn({
+			let path = path.clone();
+			let requests = requests.clone();
+			move || {
+				requests
+					.into_iter()
+					.map(|command| send_to(&path, &Request { buddy: 1, command }))
+					.collect::<Vec<_>>()
+			}
+		});
+
+		let mut received = Vec::new();
+		while !client.is_finished() {
+			server.poll(|request| {
+				let response = match &request.command {
+					Command::SetBehavior { .. } => Response::error("stuck"),
+					_ => Response::ok(),
+				};
+				received.push(request);
+				response
+			});
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+		let responses = client.join().unwrap();
+		drop(server);
+		let _ = std::fs::remove_dir(&dir);
+
+		assert_eq!(received.len(), requests.len());
+		for (request, sent) in received.iter().zip(&requests) {
+			assert_eq!(request.buddy, 1);
+			assert_eq!(format!("{:?}", request.command), format!("{sent:?}"));
+		}
+		let responses: Vec<_> = responses
+			.into_iter()
+			.map(|response| {
+				let response = response.unwrap();
+				(response.ok, response.error)
+			})
+			.collect();
+		assert_eq!(
+			responses,
+			[
+				(true, None),
+				(false, Some("stuck".to_string())),
+				(true, None)
+			]
+		);
+		assert!(!path.exists(), "socket left behind");
+	}
+
+	#[test]
+	fn drops_clients_sending_endless_lines() {
+		let dir = std::env::temp_dir().join(format!("funfriend-flood-{}", std::process::id()));
+		let path = dir.join(SOCKET_FILENAME);
+		let mut server = ControlServer::bind_at(path.clone()).unwrap();
+		let mode = std::fs::metadata(&dir).unwrap().mode();
+		assert_eq!(mode & 0o777, 0o700);
+
+		let client = std::thread::spawn({
+			let path = path.clone();
+			move || {
+				let mut stream = UnixStream::connect(&path).unwrap();
+				// The server hangs up partway through once it has seen enough.
+				let _ = stream.write_all(&[b'x'; MAX_LINE_LENGTH * 2]);
+				let mut answer = String::new();
+				stream.read_to_string(&mut answer).unwrap();
+				answer
+			}
+		});
+		while !client.is_finished() {
+			server.poll(|_| panic!("no line was sent"));
+			std::thread::sleep(std::time::Duration::from_millis(1));
+		}
+		assert!(server.clients.is_empty(), "client still connected");
+
+		let answer = client.join().unwrap();
+		let response: Response = serde_json::from_str(&answer).unwrap();
+		assert_eq!(response.error.as_deref(), Some("request too long"));
+		drop(server);
+		let _ = std::fs::remove_dir(&dir);
+	}
+}
@@ -0,0 +1,256 @@
+//! Inline markup for dialog lines.
+//!
+//! `[color=#f00]`, `[shake]` and `[wave]` style the text up to their matching
+//! `[/color]`, `[/shake]` or `[/wave]` and can be nested. `[pause=0.5]` waits that
+//! many seconds before revealing the rest. `[[` is a literal `[`, and anything
+//! that isn't a known tag is kept as plain text.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+	pub color: [f32; 4],
+	/// Glyphs jitter around their position.
+	pub shake: bool,
+	/// Glyphs bob up and down one after another.
+	pub wave: bool,
+}
+
+impl Default for Style {
+	fn default() -> Self {
+		Self {
+			color: [1.0, 1.0, 1.0, 1.0],
+			shake: false,
+			wave: false,
+		}
+	}
+}
+
+/// A piece of text drawn in a single style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+	pub text: String,
+	pub style: Style,
+	/// Seconds to wait before revealing this run.
+	pub pause: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StyledText {
+	pub runs: Vec<Run>,
+}
+
+#[derive(Clone, Copy)]
+enum Effect {
+	Color([f32; 4]),
+	Shake,
+	Wave,
+}
+
+enum Tag {
+	Open(&'static str, Effect),
+	Close(&'static str),
+	Pause(f64),
+}
+
+impl StyledText {
+	pub fn parse(source: &str) -> Self {
+		let mut runs = vec![Run {
+			text: String::new(),
+			style: Style::default(),
+			pause: 0.0,
+		}];
+		// Open tags, innermost last. Closing one only undoes its own effect.
+		let mut stack: Vec<(&str, Effect)> = Vec::new();
+
+		let mut rest = source;
+		while let Some(start) = rest.find('[') {
+			runs.last_mut().unwrap().text.push_str(&rest[..start]);
+			rest = &rest[start..];
+
+			if let Some(escaped) = rest.strip_prefix("[[") {
+				runs.last_mut().unwrap().text.push('[');
+				rest = escaped;
+				continue;
+			}
+
+			let tag = rest
+				.find(']')
+				.and_then(|end| Some((Self::parse_tag(&rest[1..end])?, end)));
+			let Some((tag, end)) = tag else {
+				runs.last_mut().unwrap().text.push('[');
+				rest = &rest[1..];
+				continue;
+			};
+			rest = &rest[end + 1..];
+
+			let mut pause = 0.0;
+			match tag {
+				Tag::Open(name, effect) => stack.push((name, effect)),
+				Tag::Close(name) => {
+					if let Some(i) = stack.iter().rposition(|&(open, _)| open == name) {
+						stack.remove(i);
+					}
+				}
+				Tag::Pause(seconds) => pause = seconds,
+			}
+
+			let mut style = Style::default();
+			for &(_, effect) in &stack {
+				match effect {
+					Effect::Color(color) => style.color = color,
+					Effect::Shake => style.shake = true,
+					Effect::Wave => style.wave = true,
+				}
+			}
+			let last = runs.last_mut().unwrap();
+			if last.text.is_empty() {
+				last.style = style;
+				last.pause += pause;
+			} else {
+				runs.push(Run {
+					text: String::new(),
+					style,
+					pause,
+				});
+			}
+		}
+		runs.last_mut().unwrap().text.push_str(rest);
+
+		Self { runs }
+	}
+
+	/// Parses the inside of `[...]`, or `None` if it isn't a tag we know.
+	fn parse_tag(tag: &str) -> Option<Tag> {
+		let (name, value) = match tag.split_once('=') {
+			Some((name, value)) => (name, Some(value)),
+			None => (tag, None),
+		};
+		let tag = match (name, value) {
+			("/color", None) => Tag::Close("color"),
+			("/shake", None) => Tag::Close("shake"),
+			("/wave", None) => Tag::Close("wave"),
+			("color", Some(value)) => Tag::Open("color", Effect::Color(parse_color(value)?)),
+			("shake", None) => Tag::Open("shake", Effect::Shake),
+			("wave", None) => Tag::Open("wave", Effect::Wave),
+			("pause", Some(value)) => {
+				let seconds: f64 = value.parse().ok()?;
+				if !seconds.is_finite() || seconds < 0.0 {
+					return None;
+				}
+				Tag::Pause(seconds)
+			}
+			_ => return None,
+		};
+		Some(tag)
+	}
+
+	/// The text with all markup removed.
+	pub fn text(&self) -> String {
+		self.runs.iter().map(|run| run.text.as_str()).collect()
+	}
+
+	/// Style of the char at `index`, counted in chars of [`Self::text`].
+	pub fn style_at(&self, index: usize) -> Style {
+		self.run_at(index).map_or(Style::default(), |run| run.style)
+	}
+
+	/// The char at `index`, counted in chars of [`Self::text`].
+	pub fn char_at(&self, index: usize) -> Option<char> {
+		self.runs.iter().flat_map(|run| run.text.chars()).nth(index)
+	}
+
+	/// Seconds of pauses placed before any of the chars in `chars`.
+	pub fn pauses(&self, chars: std::ops::Range<usize>) -> f64 {
+		let mut start = 0;
+		let mut pause = 0.0;
+		for run in &self.runs {
+			if chars.contains(&start) {
+				pause += run.pause;
+			}
+			start += run.text.chars().count();
+		}
+		pause
+	}
+
+	fn run_at(&self, index: usize) -> Option<&Run> {
+		let mut end = 0;
+		self.runs.iter().find(|run| {
+			end += run.text.chars().count();
+			index < end
+		})
+	}
+}
+
+/// `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+fn parse_color(value: &str) -> Option<[f32; 4]> {
+	let hex = value.strip_prefix('#')?;
+	if !hex.is_ascii() {
+		return None;
+	}
+	let digits: Vec<u8> = match hex.len() {
+		3 | 4 => hex
+			.chars()
+			.map(|digit| u8::from_str_radix(&digit.to_string(), 16).map(|d| d * 17))
+			.collect::<Result<_, _>>()
+			.ok()?,
+		6 | 8 => (0..hex.len())
+			.step_by(2)
+			.map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+			.collect::<Result<_, _>>()
+			.ok()?,
+		_ => return None,
+	};
+
+	let mut color = [1.0; 4];
+	for (channel, digit) in color.iter_mut().zip(digits) {
+		*channel = digit as f32 / 255.0;
+	}
+	Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tags_style_and_pause_the_text_they_wrap() {
+		let text =
+			StyledText::parse("BUT ALSO [color=#f00]PLEASE[/color][pause=0.5] [shake]GO[/shake]");
+		assert_eq!(text.text(), "BUT ALSO PLEASE GO");
+
+		let please = text.style_at(9);
+		assert_eq!(please.color, [1.0, 0.0, 0.0, 1.0]);
+		assert!(!please.shake);
+		assert_eq!(text.style_at(15), Style::default());
+		assert!(text.style_at(16).shake);
+
+		// The pause comes right after "PLEASE".
+		assert_eq!(text.pauses(0..15), 0.0);
+		assert_eq!(text.pauses(15..16), 0.5);
+		assert_eq!(text.char_at(16), Some('G'));
+	}
+
+	#[test]
+	fn effects_nest_and_close_independently() {
+		let text = StyledText::parse("[wave]A[color=#00ff0080]B[/wave]C[/color]D");
+		assert_eq!(text.text(), "ABCD");
+		let green = [0.0, 1.0, 0.0, 128.0 / 255.0];
+		let styles: Vec<_> = (0..4)
+			.map(|i| {
+				let style = text.style_at(i);
+				(style.wave, style.color)
+			})
+			.collect();
+		let white = Style::default().color;
+		assert_eq!(
+			styles,
+			[(true, white), (true, green), (false, green), (false, white)]
+		);
+	}
+
+	#[test]
+	fn unknown_tags_and_escapes_stay_as_text() {
+		let text = StyledText::parse("[[shake] [bold]X[/bold] [pause=-1]");
+		assert_eq!(text.text(), "[shake] [bold]X[/bold] [pause=-1]");
+		assert_eq!(text.runs.len(), 1);
+	}
+}
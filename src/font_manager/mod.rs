@@ -0,0 +1,1054 @@
+use std::{cell::RefCell, collections::HashMap, path::Path, str::FromStr};
+
+pub mod markup;
+mod ttf;
+
+/// Drawn for chars that no sheet in the chain has.
+const REPLACEMENT_CHAR: char = '?';
+
+thread_local! {
+	/// Rasterized TrueType sheets by path and size, so every speech bubble doesn't
+	/// rasterize the whole font again.
+	static TTF_SHEETS: RefCell<HashMap<(String, u32), BMSheet>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone)]
+pub struct BMCommon {
+	pub line_height: i32,
+	pub base: i32,
+	pub scale_w: i32,
+	pub scale_h: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BMChar {
+	pub id: i32,
+	pub x: i32,
+	pub y: i32,
+	pub width: i32,
+	pub height: i32,
+	pub x_offset: i32,
+	pub y_offset: i32,
+	pub x_advance: i32,
+	/// Index into [`BMSheet::pages`] of the texture the glyph is on.
+	pub page: i32,
+}
+
+/// Where a page texture comes from.
+#[derive(Debug, Clone)]
+pub enum Page {
+	File(String),
+	/// Rasterized at runtime, e.g. from a TrueType font.
+	Image(image::RgbaImage),
+}
+
+#[derive(Debug, Clone)]
+pub struct BMKerning {
+	pub first: i32,
+	pub second: i32,
+	pub amount: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BMSheet {
+	pub common: BMCommon,
+	/// Kerning amounts indexed by `(first, second)` char id.
+	pub kerning_pairs: HashMap<(i32, i32), i32>,
+	/// Chars indexed by the char they draw.
+	pub char_index: HashMap<char, BMChar>,
+	/// Textures glyphs are cut from, indexed by page id.
+	pub pages: Vec<Page>,
+	/// Sheets to take chars from when this one doesn't have them, in order.
+	pub fallbacks: Vec<BMSheet>,
+}
+
+impl BMSheet {
+	/// Extra horizontal offset to apply between two adjacent chars, by char id.
+	pub fn kerning(&self, first: i32, second: i32) -> i32 {
+		self.kerning_pairs
+			.get(&(first, second))
+			.copied()
+			.unwrap_or(0)
+	}
+
+	/// This sheet followed by its fallbacks. Indices into this are what
+	/// [`PositionedChar::sheet`] refers to.
+	pub fn chain(&self) -> impl Iterator<Item = &BMSheet> {
+		std::iter::once(self).chain(self.fallbacks.iter())
+	}
+
+	/// Looks up `char`, trying the other case too so uppercase-only fonts still
+	/// draw lowercase text.
+	fn find_folded(&self, char: char) -> Option<&BMChar> {
+		fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+			let char = chars.next()?;
+			chars.next().is_none().then_some(char)
+		}
+
+		self.char_index.get(&char).or_else(|| {
+			[single(char.to_uppercase()), single(char.to_lowercase())]
+				.into_iter()
+				.flatten()
+				.find_map(|folded| self.char_index.get(&folded))
+		})
+	}
+
+	/// Finds the glyph to draw for `char` along with the index of the sheet in
+	/// [`Self::chain`] it comes from. Falls back to [`REPLACEMENT_CHAR`] when no
+	/// sheet has it, and to nothing at all if none has that either.
+	pub fn glyph(&self, char: char) -> Option<(usize, &BMChar)> {
+		let find = |char| {
+			self.chain()
+				.enumerate()
+				.find_map(|(i, sheet)| sheet.find_folded(char).map(|bm_char| (i, bm_char)))
+		};
+		find(char).or_else(|| {
+			tracing::debug!("no glyph for {char:?}, using {REPLACEMENT_CHAR:?}");
+			find(REPLACEMENT_CHAR)
+		})
+	}
+
+	/// How far the pen moves for `char` when it follows `previous`, kerning included.
+	pub fn advance(&self, previous: Option<char>, char: char) -> i32 {
+		let Some((sheet, bm_char)) = self.glyph(char) else {
+			return 0;
+		};
+		bm_char.x_advance + self.kerning_between(previous, sheet, bm_char)
+	}
+
+	fn kerning_between(&self, previous: Option<char>, sheet: usize, bm_char: &BMChar) -> i32 {
+		// Kerning only makes sense between two glyphs from the same sheet.
+		match previous.and_then(|previous| self.glyph(previous)) {
+			Some((previous_sheet, previous)) if previous_sheet == sheet => self
+				.chain()
+				.nth(sheet)
+				.map_or(0, |sheet| sheet.kerning(previous.id, bm_char.id)),
+			_ => 0,
+		}
+	}
+}
+
+/// Horizontal alignment of each line within a [`TextLayout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+	#[default]
+	Left,
+	Center,
+}
+
+/// A line of wrapped text, every char along with its index in the whole text.
+pub type Line = Vec<(usize, char)>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayoutOptions {
+	/// Lines longer than this many pixels are wrapped between words.
+	pub max_width: Option<i32>,
+	pub align: Align,
+}
+
+/// A glyph placed in layout space, with `(0, 0)` at the bottom-left and y pointing up.
+#[derive(Debug, Clone)]
+pub struct PositionedChar {
+	pub x: i32,
+	pub y: i32,
+	pub char: BMChar,
+	/// Index of the sheet in [`BMSheet::chain`] the glyph comes from.
+	pub sheet: usize,
+	/// Index of the char in the laid out text, counted in chars.
+	pub index: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+	/// Width of the widest line.
+	pub width: i32,
+	/// Height of all lines together.
+	pub height: i32,
+	pub glyphs: Vec<PositionedChar>,
+}
+
+/// A `name key=value ...` entry from a text or XML font file.
+struct Tag {
+	line: usize,
+	name: String,
+	args: HashMap<String, String>,
+}
+
+impl Tag {
+	fn get<T: FromStr>(&self, key: &str) -> Result<T, String> {
+		let value = self
+			.args
+			.get(key)
+			.ok_or_else(|| format!("line {}: `{}` is missing `{key}`", self.line, self.name))?;
+		value.parse().map_err(|_| {
+			format!(
+				"line {}: invalid `{key}` value {value:?} in `{}`",
+				self.line, self.name
+			)
+		})
+	}
+
+	fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+		if self.args.contains_key(key) {
+			self.get(key)
+		} else {
+			Ok(default)
+		}
+	}
+}
+
+pub struct FontMan;
+
+impl FontMan {
+	/// Parses a font in any of the BMFont formats: text, XML or binary version 3.
+	pub fn parse(data: &[u8]) -> Result<BMSheet, String> {
+		if data.starts_with(b"BMF") {
+			return Self::parse_bm_binary(data);
+		}
+
+		let text = std::str::from_utf8(data)
+			.map_err(|err| format!("not a text, XML or binary BMFont file: {err}"))?
+			.trim_start_matches('\u{feff}');
+		if text.trim_start().starts_with('<') {
+			Self::parse_bm_xml(text)
+		} else {
+			Self::parse_bm(text)
+		}
+	}
+
+	/// Parses the text BMFont format.
+	pub fn parse_bm(data: &str) -> Result<BMSheet, String> {
+		let tags = data
+			.lines()
+			.enumerate()
+			.filter_map(|(i, line)| {
+				let (name, args) = line.trim().split_once(char::is_whitespace)?;
+				Some(Tag {
+					line: i + 1,
+					name: name.to_string(),
+					args: FontMan::parse_args(&args.to_string()),
+				})
+			})
+			.collect::<Vec<_>>();
+		Self::sheet_from_tags(&tags)
+	}
+
+	/// Parses the XML BMFont format. Every element is read the same way as a line of
+	/// the text format, nesting is ignored.
+	pub fn parse_bm_xml(data: &str) -> Result<BMSheet, String> {
+		let mut tags = Vec::new();
+		let mut rest = data;
+		let mut line = 1;
+		while let Some(start) = rest.find('<') {
+			line += rest[..start].matches('\n').count();
+			let end = rest[start..]
+				.find('>')
+				.ok_or_else(|| format!("line {line}: unterminated tag"))?;
+			let element = &rest[start + 1..start + end];
+			let tag_line = line;
+			line += element.matches('\n').count();
+			rest = &rest[start + end + 1..];
+
+			if element.starts_with(['?', '!', '/']) {
+				continue;
+			}
+			let element = element.trim_end_matches('/').trim();
+			let (name, args) = element
+				.split_once(char::is_whitespace)
+				.unwrap_or((element, ""));
+			tags.push(Tag {
+				line: tag_line,
+				name: name.to_string(),
+				args: FontMan::parse_args(&args.to_string()),
+			});
+		}
+		Self::sheet_from_tags(&tags)
+	}
+
+	/// Parses the binary BMFont format, version 3.
+	pub fn parse_bm_binary(data: &[u8]) -> Result<BMSheet, String> {
+		let u16_at = |b: &[u8], at: usize| u16::from_le_bytes([b[at], b[at + 1]]) as i32;
+		let i16_at = |b: &[u8], at: usize| i16::from_le_bytes([b[at], b[at + 1]]) as i32;
+		let u32_at = |b: &[u8], at: usize| {
+			u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]]) as i32
+		};
+
+		match data.get(..4) {
+			Some([b'B', b'M', b'F', 3]) => {}
+			Some([b'B', b'M', b'F', version]) => {
+				return Err(format!("unsupported binary BMFont version {version}"))
+			}
+			_ => return Err("not a binary BMFont file".to_string()),
+		}
+
+		let mut common = None;
+		let mut pages = Vec::new();
+		let mut chars = Vec::new();
+		let mut kernings = Vec::new();
+
+		let mut offset = 4;
+		while offset < data.len() {
+			let header = data
+				.get(offset..offset + 5)
+				.ok_or_else(|| format!("byte {offset}: truncated block header"))?;
+			let kind = header[0];
+			let past_end = || format!("byte {offset}: block {kind} runs past the end of the file");
+			let size = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+			let end = usize::try_from(size)
+				.ok()
+				.and_then(|size| (offset + 5).checked_add(size))
+				.ok_or_else(past_end)?;
+			let block = data.get(offset + 5..end).ok_or_else(past_end)?;
+			let record_error =
+				|what: &str| format!("byte {offset}: block {kind} has a truncated {what} record");
+
+			match kind {
+				2 => {
+					if block.len() < 8 {
+						return Err(record_error("common"));
+					}
+					common = Some(BMCommon {
+						line_height: u16_at(block, 0),
+						base: u16_at(block, 2),
+						scale_w: u16_at(block, 4),
+						scale_h: u16_at(block, 6),
+					});
+				}
+				3 => {
+					pages = block
+						.split(|&b| b == 0)
+						.filter(|name| !name.is_empty())
+						.map(|name| Page::File(String::from_utf8_lossy(name).into_owned()))
+						.collect();
+				}
+				4 => {
+					for record in block.chunks(20) {
+						if record.len() < 20 {
+							return Err(record_error("char"));
+						}
+						let id = u32_at(record, 0);
+						chars.push(BMChar {
+							id,
+							x: u16_at(record, 4),
+							y: u16_at(record, 6),
+							width: u16_at(record, 8),
+							height: u16_at(record, 10),
+							x_offset: i16_at(record, 12),
+							y_offset: i16_at(record, 14),
+							x_advance: i16_at(record, 16),
+							page: record[18] as i32,
+						});
+					}
+				}
+				5 => {
+					for record in block.chunks(10) {
+						if record.len() < 10 {
+							return Err(record_error("kerning"));
+						}
+						kernings.push(BMKerning {
+							first: u32_at(record, 0),
+							second: u32_at(record, 4),
+							amount: i16_at(record, 8),
+						});
+					}
+				}
+				_ => {}
+			}
+			offset = end;
+		}
+
+		let common = common.ok_or("missing common block")?;
+		Self::build_sheet(common, pages, chars, kernings)
+	}
+
+	fn sheet_from_tags(tags: &[Tag]) -> Result<BMSheet, String> {
+		let mut common = None;
+		let mut pages = Vec::new();
+		let mut chars = Vec::new();
+		let mut kernings = Vec::new();
+
+		for tag in tags {
+			match tag.name.as_str() {
+				"common" => {
+					common = Some(BMCommon {
+						line_height: tag.get("lineHeight")?,
+						base: tag.get("base")?,
+						scale_w: tag.get("scaleW")?,
+						scale_h: tag.get("scaleH")?,
+					});
+				}
+				"page" => pages.push((tag.get::<i32>("id")?, tag.get::<String>("file")?)),
+				"char" => {
+					let id = tag.get("id")?;
+					chars.push(BMChar {
+						id,
+						x: tag.get("x")?,
+						y: tag.get("y")?,
+						width: tag.get("width")?,
+						height: tag.get("height")?,
+						x_offset: tag.get("xoffset")?,
+						y_offset: tag.get("yoffset")?,
+						x_advance: tag.get("xadvance")?,
+						page: tag.get_or("page", 0)?,
+					});
+				}
+				"kerning" => kernings.push(BMKerning {
+					first: tag.get("first")?,
+					second: tag.get("second")?,
+					amount: tag.get("amount")?,
+				}),
+				_ => {}
+			}
+		}
+
+		let common = common.ok_or("missing `common` line")?;
+		pages.sort_by_key(|(id, _)| *id);
+		for (i, (id, _)) in pages.iter().enumerate() {
+			if *id != i as i32 {
+				return Err(format!("page ids must count up from 0, found id {id}"));
+			}
+		}
+		let pages = pages
+			.into_iter()
+			.map(|(_, file)| Page::File(file))
+			.collect();
+		Self::build_sheet(common, pages, chars, kernings)
+	}
+
+	fn build_sheet(
+		common: BMCommon,
+		pages: Vec<Page>,
+		chars: Vec<BMChar>,
+		kernings: Vec<BMKerning>,
+	) -> Result<BMSheet, String> {
+		// A file without page entries still has the one page, `{font}.png`.
+		let page_count = pages.len().max(1);
+		if let Some(bm_char) = chars
+			.iter()
+			.find(|bm_char| bm_char.page < 0 || bm_char.page as usize >= page_count)
+		{
+			return Err(format!(
+				"char {} is on page {}, but there are only {} pages",
+				bm_char.id, bm_char.page, page_count
+			));
+		}
+
+		let kerning_pairs = kernings
+			.iter()
+			.map(|kerning| ((kerning.first, kerning.second), kerning.amount))
+			.collect();
+		// Index by id rather than `letter`, which BMFont writes as "space" for ' '.
+		let char_index = chars
+			.into_iter()
+			.filter_map(|bm_char| char::from_u32(bm_char.id as u32).map(|char| (char, bm_char)))
+			.collect();
+		Ok(BMSheet {
+			common,
+			kerning_pairs,
+			char_index,
+			pages,
+			fallbacks: Vec::new(),
+		})
+	}
+
+	fn parse_args(data: &String) -> HashMap<String, String> {
+		// tracing::info!("Got data to parse: {}", data);
+
+		let mut args = HashMap::new();
+		let mut chars = data.chars().peekable();
+		let mut current_key = String::new();
+		let mut current_value = String::new();
+		let mut in_quotes = false;
+		let mut parsing_key = true; // Flag to differentiate between key and value parsing
+		let mut is_value = false; // To track when we're parsing values after '='
+
+		// Iterate through each character in the string
+		while let Some(ch) = chars.next() {
+			match ch {
+				'=' if parsing_key => {
+					// Once we encounter '=', switch to value parsing mode
+					parsing_key = false;
+					is_value = true; // Now we will be capturing the value
+				}
+				'"' => {
+					// Handle quotes for value
+					if in_quotes {
+						// Closing quote, store value if key and value are set
+						args.insert(current_key.clone(), current_value.clone());
+						current_key.clear();
+						current_value.clear();
+						parsing_key = true; // We expect a new key now
+					}
+					in_quotes = !in_quotes; // Toggle the quote flag
+				}
+				' ' | '\t' | '\n' => {
+					// Skip whitespace, unless we are inside quotes or currently parsing a value
+					if !in_quotes
+						&& !parsing_key && !current_key.is_empty()
+						&& !current_value.is_empty()
+					{
+						// Finished parsing a key-value pair
+						args.insert(current_key.clone(), current_value.clone());
+						current_key.clear();
+						current_value.clear();
+						parsing_key = true; // Now we expect a new key
+					}
+				}
+				_ => {
+					// Accumulate characters for key or value
+					if parsing_key {
+						current_key.push(ch); // If parsing key, build the key
+					} else if in_quotes {
+						current_value.push(ch); // If in quotes, accumulate the value
+					} else {
+						current_value.push(ch); // Otherwise, accumulate the value normally
+					}
+				}
+			}
+		}
+
+		// Handle any remaining key-value pair
+		if !current_key.is_empty() && !current_value.is_empty() {
+			args.insert(current_key, current_value);
+		}
+		// tracing::info!("Got args: {:?}", args);
+		args
+	}
+
+	/// Loads a font for layout and rendering. `.ttf` and `.otf` files are rasterized
+	/// at `size` pixels, anything else is taken as a BMFont path prefix and read from
+	/// `{font}.fnt` in any BMFont format, at whatever size it was baked at.
+	pub fn load(font: &str, size: f32) -> Result<BMSheet, String> {
+		if ttf::is_ttf(font) {
+			let key = (font.to_string(), size.to_bits());
+			if let Some(sheet) = TTF_SHEETS.with_borrow(|sheets| sheets.get(&key).cloned()) {
+				return Ok(sheet);
+			}
+			let sheet = ttf::load(font, size)?;
+			TTF_SHEETS.with_borrow_mut(|sheets| sheets.insert(key, sheet.clone()));
+			return Ok(sheet);
+		}
+
+		let path = format!("{font}.fnt");
+		let data = std::fs::read(&path).map_err(|err| format!("failed to read {path}: {err}"))?;
+		let mut sheet = Self::parse(&data).map_err(|err| format!("{path}: {err}"))?;
+
+		// Page textures are looked up next to the font, or `{font}.png` if it lists none.
+		let dir = Path::new(&path).parent().unwrap_or(Path::new(""));
+		for page in sheet.pages.iter_mut() {
+			if let Page::File(file) = page {
+				*file = dir.join(&*file).to_string_lossy().into_owned();
+			}
+		}
+		if sheet.pages.is_empty() {
+			sheet.pages.push(Page::File(format!("{font}.png")));
+		}
+		for page in &sheet.pages {
+			if let Page::File(file) = page {
+				if !Path::new(file).is_file() {
+					return Err(format!("{path}: page {file} not found"));
+				}
+			}
+		}
+		Ok(sheet)
+	}
+
+	/// Like [`Self::load`], with `fallbacks` chained on for chars `font` doesn't have.
+	/// Fallbacks that fail to load are skipped.
+	pub fn load_with_fallbacks(
+		font: &str,
+		size: f32,
+		fallbacks: &[String],
+	) -> Result<BMSheet, String> {
+		let mut sheet = Self::load(font, size)?;
+		for fallback in fallbacks.iter().filter(|fallback| *fallback != font) {
+			match Self::load(fallback, size) {
+				Ok(fallback) => sheet.fallbacks.push(fallback),
+				Err(err) => tracing::warn!("skipping fallback font: {err}"),
+			}
+		}
+		Ok(sheet)
+	}
+
+	/// Width of a single line of text including kerning, ignoring newlines.
+	pub fn text_width(text: &str, sheet: &BMSheet) -> i32 {
+		Self::chars_width(text.chars(), sheet)
+	}
+
+	fn chars_width(chars: impl Iterator<Item = char>, sheet: &BMSheet) -> i32 {
+		let mut width = 0;
+		let mut previous = None;
+		for char in chars.filter(|&char| char != '\n') {
+			width += sheet.advance(previous, char);
+			previous = Some(char);
+		}
+		width
+	}
+
+	/// Splits text into lines on newlines and, if `max_width` is set, between words
+	/// so no line is wider than it. Words that don't fit on a line of their own are
+	/// broken between letters. Every char keeps its index in `text`.
+	pub fn wrap_text(text: &str, sheet: &BMSheet, max_width: Option<i32>) -> Vec<Line> {
+		let chars: Vec<(usize, char)> = text.chars().enumerate().collect();
+		let paragraphs = chars.split(|&(_, char)| char == '\n');
+		let Some(max_width) = max_width else {
+			return paragraphs.map(<[_]>::to_vec).collect();
+		};
+
+		let width =
+			|chars: &[(usize, char)]| Self::chars_width(chars.iter().map(|&(_, c)| c), sheet);
+		let space_width = Self::text_width(" ", sheet);
+		let mut lines = Vec::new();
+		for paragraph in paragraphs {
+			let mut line: Line = Vec::new();
+			let mut line_width = 0;
+			// Index in `paragraph` of the word's first char.
+			let mut start = 0;
+			for word in paragraph.split(|&(_, char)| char == ' ') {
+				if !line.is_empty() && line_width + space_width + width(word) > max_width {
+					lines.push(std::mem::take(&mut line));
+					line_width = 0;
+				}
+				if !line.is_empty() {
+					line.push(paragraph[start - 1]);
+					line_width += space_width;
+				}
+
+				for &(index, char) in word {
+					let mut advance = sheet.advance(line.last().map(|&(_, c)| c), char);
+					if line_width > 0 && line_width + advance > max_width {
+						lines.push(std::mem::take(&mut line));
+						line_width = 0;
+						advance = sheet.advance(None, char);
+					}
+					line.push((index, char));
+					line_width += advance;
+				}
+				start += word.len() + 1;
+			}
+			lines.push(line);
+		}
+		lines
+	}
+
+	/// Lays out text line by line, advancing the pen by each glyph's `xadvance`.
+	pub fn layout_text(text: &str, sheet: &BMSheet, options: &LayoutOptions) -> TextLayout {
+		let lines = Self::wrap_text(text, sheet, options.max_width);
+		let line_widths: Vec<i32> = lines
+			.iter()
+			.map(|line| {
+				let end = line.iter().rposition(|&(_, char)| char != ' ');
+				let chars = &line[..end.map_or(0, |end| end + 1)];
+				Self::chars_width(chars.iter().map(|&(_, char)| char), sheet)
+			})
+			.collect();
+		let width = line_widths.iter().copied().max().unwrap_or(0);
+		let height = sheet.common.line_height * lines.len() as i32;
+
+		let mut glyphs = Vec::new();
+		for (i, (line, line_width)) in lines.iter().zip(&line_widths).enumerate() {
+			let mut x = match options.align {
+				Align::Left => 0,
+				Align::Center => (width - line_width) / 2,
+			};
+			let line_y = height - sheet.common.line_height * (i as i32 + 1);
+
+			let mut previous = None;
+			for &(text_index, char) in line {
+				let Some((index, bm_char)) = sheet.glyph(char) else {
+					previous = Some(char);
+					continue;
+				};
+				x += sheet.kerning_between(previous, index, bm_char);
+				previous = Some(char);
+
+				// Glyphs from a fallback sheet sit on that sheet's baseline.
+				let glyph_sheet = sheet.chain().nth(index).unwrap();
+				glyphs.push(PositionedChar {
+					x: x + bm_char.x_offset,
+					y: line_y + glyph_sheet.common.base - bm_char.height - bm_char.y_offset,
+					char: bm_char.clone(),
+					sheet: index,
+					index: text_index,
+				});
+				x += bm_char.x_advance;
+			}
+		}
+
+		TextLayout {
+			width,
+			height,
+			glyphs,
+		}
+	}
+
+	pub fn get_letter_crop(char: &BMChar, sheet: &BMSheet) -> (f32, f32, f32, f32) {
+		let x = char.x as f32 / sheet.common.scale_w as f32;
+		let y = char.y as f32 / sheet.common.scale_h as f32;
+		let w = char.width as f32 / sheet.common.scale_w as f32;
+		let h = char.height as f32 / sheet.common.scale_h as f32;
+
+		(x, y, w, h)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FONTS: [&str; 2] = ["assets/fonts/SpaceMono", "assets/fonts/Newspaper"];
+
+	fn read(font: &str) -> String {
+		std::fs::read_to_string(format!("{font}.fnt")).unwrap()
+	}
+
+	/// The text format turned into XML, one element per line like BMFont writes it.
+	fn to_xml(text: &str) -> String {
+		let mut xml = String::from("<?xml version=\"1.0\"?>\n<font>\n");
+		for line in text.lines().filter(|line| !line.trim().is_empty()) {
+			xml += &format!("  <{} />\n", line.trim());
+		}
+		xml + "</font>\n"
+	}
+
+	fn block(kind: u8, data: &[u8]) -> Vec<u8> {
+		let mut block = vec![kind];
+		block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+		block.extend_from_slice(data);
+		block
+	}
+
+	/// `sheet` written back out in the binary format, version 3.
+	fn to_binary(sheet: &BMSheet) -> Vec<u8> {
+		let mut data = b"BMF\x03".to_vec();
+		let common = &sheet.common;
+		let mut block_data = Vec::new();
+		for value in [
+			common.line_height,
+			common.base,
+			common.scale_w,
+			common.scale_h,
+			1,
+		] {
+			block_data.extend_from_slice(&(value as u16).to_le_bytes());
+		}
+		block_data.extend_from_slice(&[0; 5]);
+		data.extend(block(2, &block_data));
+
+		let mut block_data = Vec::new();
+		for page in &sheet.pages {
+			let Page::File(file) = page else { continue };
+			block_data.extend_from_slice(file.as_bytes());
+			block_data.push(0);
+		}
+		data.extend(block(3, &block_data));
+
+		let mut chars: Vec<&BMChar> = sheet.char_index.values().collect();
+		chars.sort_by_key(|bm_char| bm_char.id);
+		let mut block_data = Vec::new();
+		for bm_char in chars {
+			block_data.extend_from_slice(&(bm_char.id as u32).to_le_bytes());
+			for value in [bm_char.x, bm_char.y, bm_char.width, bm_char.height] {
+				block_data.extend_from_slice(&(value as u16).to_le_bytes());
+			}
+			for value in [bm_char.x_offset, bm_char.y_offset, bm_char.x_advance] {
+				block_data.extend_from_slice(&(value as i16).to_le_bytes());
+			}
+			block_data.extend_from_slice(&[bm_char.page as u8, 15]);
+		}
+		data.extend(block(4, &block_data));
+
+		let mut block_data = Vec::new();
+		for (&(first, second), &amount) in &sheet.kerning_pairs {
+			block_data.extend_from_slice(&(first as u32).to_le_bytes());
+			block_data.extend_from_slice(&(second as u32).to_le_bytes());
+			block_data.extend_from_slice(&(amount as i16).to_le_bytes());
+		}
+		data.extend(block(5, &block_data));
+		data
+	}
+
+	/// Common values, chars, kerning pairs and page files.
+	type Summary = (Vec<i32>, Vec<[i32; 9]>, Vec<((i32, i32), i32)>, Vec<String>);
+
+	/// What layout and rendering read from a sheet, in a comparable form.
+	fn summary(sheet: &BMSheet) -> Summary {
+		let common = &sheet.common;
+		let mut chars: Vec<[i32; 9]> = sheet
+			.char_index
+			.values()
+			.map(|c| {
+				let (x, y, w, h) = (c.x, c.y, c.width, c.height);
+				[
+					c.id,
+					x,
+					y,
+					w,
+					h,
+					c.x_offset,
+					c.y_offset,
+					c.x_advance,
+					c.page,
+				]
+			})
+			.collect();
+		chars.sort();
+		let mut kernings: Vec<_> = sheet.kerning_pairs.iter().map(|(k, v)| (*k, *v)).collect();
+		kernings.sort();
+		let pages = sheet
+			.pages
+			.iter()
+			.map(|page| match page {
+				Page::File(file) => file.clone(),
+				Page::Image(_) => String::new(),
+			})
+			.collect();
+		let common = vec![
+			common.line_height,
+			common.base,
+			common.scale_w,
+			common.scale_h,
+		];
+		(common, chars, kernings, pages)
+	}
+
+	#[test]
+	fn parses_the_bundled_fonts_in_every_format() {
+		for font in FONTS {
+			let text = read(font);
+			let sheet = FontMan::parse(text.as_bytes()).unwrap();
+			assert_eq!(sheet.char_index.len(), 96, "{font}");
+			assert!(matches!(&sheet.pages[..], [Page::File(_)]), "{font}");
+
+			let xml = FontMan::parse(to_xml(&text).as_bytes()).unwrap();
+			assert_eq!(summary(&xml), summary(&sheet), "{font} as XML");
+			let binary = FontMan::parse(&to_binary(&sheet)).unwrap();
+			assert_eq!(summary(&binary), summary(&sheet), "{font} as binary");
+		}
+	}
+
+	/// Width of `text` from the chars' advances alone.
+	fn unkerned_width(text: &str, sheet: &BMSheet) -> i32 {
+		text.chars()
+			.map(|char| sheet.char_index[&char].x_advance)
+			.sum()
+	}
+
+	#[test]
+	fn kerning_pairs_change_advance_widths() {
+		let sheet = FontMan::load(FONTS[0], 0.0).unwrap();
+		let pairs = [("fi", 'f', 'i'), ("fl", 'f', 'l')];
+		for (text, first, second) in pairs {
+			let amount = sheet.kerning(first as i32, second as i32);
+			assert_eq!(amount, -8, "{text}");
+			assert_eq!(
+				FontMan::text_width(text, &sheet),
+				unkerned_width(text, &sheet) + amount,
+				"{text}"
+			);
+		}
+		// Only the listed order is kerned.
+		assert_eq!(
+			FontMan::text_width("if", &sheet),
+			unkerned_width("if", &sheet)
+		);
+
+		let layout = FontMan::layout_text("fi", &sheet, &LayoutOptions::default());
+		let [f, i] = &layout.glyphs[..] else {
+			panic!("expected two glyphs")
+		};
+		let pen = |glyph: &PositionedChar| glyph.x - glyph.char.x_offset;
+		assert_eq!(pen(i) - pen(f), sheet.char_index[&'f'].x_advance - 8);
+	}
+
+	#[test]
+	fn fonts_without_kerning_pairs_keep_plain_advances() {
+		let sheet = FontMan::load(FONTS[1], 0.0).unwrap();
+		assert!(sheet.kerning_pairs.is_empty());
+		for text in ["fi", "fl", "AV"] {
+			assert_eq!(
+				FontMan::text_width(text, &sheet),
+				unkerned_width(text, &sheet)
+			);
+		}
+	}
+
+	#[test]
+	fn xml_errors_point_at_the_right_line() {
+		let xml = "<font>\n  <common lineHeight=\"20\" base=\"15\"\n    scaleW=\"128\" />\n  <char id=\"65\" />\n</font>";
+		let err = FontMan::parse(xml.as_bytes()).err().unwrap();
+		assert!(err.starts_with("line 2:"), "{err}");
+		let xml = xml.replace(" scaleW=\"128\"", " scaleW=\"128\" scaleH=\"128\"");
+		let err = FontMan::parse(xml.as_bytes()).err().unwrap();
+		assert!(err.starts_with("line 4:"), "{err}");
+	}
+
+	#[test]
+	fn truncated_binary_blocks_are_errors() {
+		let sheet = FontMan::parse(read(FONTS[0]).as_bytes()).unwrap();
+		let binary = to_binary(&sheet);
+		for len in [6, 30, binary.len() - 3] {
+			assert!(FontMan::parse(&binary[..len]).is_err(), "cut at {len}");
+		}
+
+		// A char block whose size isn't a whole number of records.
+		let mut data = binary[..4].to_vec();
+		data.extend(block(4, &[0; 19]));
+		let err = FontMan::parse(&data).err().unwrap();
+		assert!(err.contains("truncated char record"), "{err}");
+	}
+
+	#[test]
+	fn oversized_binary_blocks_are_errors() {
+		for size in [u32::MAX, i32::MAX as u32 + 1, 1 << 20] {
+			let mut data = b"BMF\x03\x02".to_vec();
+			data.extend_from_slice(&size.to_le_bytes());
+			data.extend_from_slice(&[0; 15]);
+			let err = FontMan::parse(&data).err().unwrap();
+			assert!(err.contains("runs past the end"), "{err}");
+		}
+	}
+
+	#[test]
+	fn missing_pages_fail_to_load() {
+		let dir = std::env::temp_dir().join(format!("funfriend-font-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let text = read(FONTS[0]).replace("SpaceMono.png", "missing.png");
+		std::fs::write(dir.join("font.fnt"), text).unwrap();
+
+		let err = FontMan::load(&dir.join("font").to_string_lossy(), 0.0)
+			.err()
+			.unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+		assert!(err.contains("missing.png not found"), "{err}");
+	}
+
+	/// Wrapped lines of `text` in SpaceMono, where every char is 8 pixels wide.
+	fn wrap(text: &str, max_width: Option<i32>) -> Vec<String> {
+		let sheet = FontMan::load(FONTS[0], 0.0).unwrap();
+		FontMan::wrap_text(text, &sheet, max_width)
+			.iter()
+			.map(|line| line.iter().map(|&(_, char)| char).collect())
+			.collect()
+	}
+
+	#[test]
+	fn wraps_between_words_at_the_width_limit() {
+		assert_eq!(wrap("AB CD EF", Some(40)), ["AB CD", "EF"]);
+		assert_eq!(wrap("AB CD EF", Some(39)), ["AB", "CD", "EF"]);
+		assert_eq!(wrap("AB CD EF", None), ["AB CD EF"]);
+	}
+
+	#[test]
+	fn breaks_words_too_long_for_a_line() {
+		assert_eq!(wrap("ABCDEFG", Some(24)), ["ABC", "DEF", "G"]);
+		assert_eq!(wrap("A BCDEFG", Some(24)), ["A", "BCD", "EFG"]);
+	}
+
+	#[test]
+	fn newlines_always_start_a_line() {
+		assert_eq!(wrap("AB\nCD", None), ["AB", "CD"]);
+		assert_eq!(wrap("AB\n\nCD", Some(100)), ["AB", "", "CD"]);
+
+		let sheet = FontMan::load(FONTS[0], 0.0).unwrap();
+		let layout = FontMan::layout_text("AB\nCD", &sheet, &LayoutOptions::default());
+		assert_eq!(layout.height, sheet.common.line_height * 2);
+		assert_eq!(layout.width, 16);
+	}
+
+	#[test]
+	fn centered_lines_are_centered_on_the_widest_one() {
+		let sheet = FontMan::load(FONTS[0], 0.0).unwrap();
+		let options = LayoutOptions {
+			max_width: None,
+			align: Align::Center,
+		};
+		let layout = FontMan::layout_text("A\nABC", &sheet, &options);
+		assert_eq!(layout.width, 24);
+		let xs: Vec<i32> = layout.glyphs.iter().map(|glyph| glyph.x).collect();
+		assert_eq!(xs, [8, 0, 8, 16]);
+		// Trailing spaces left at a wrap don't count towards the line's width.
+		let options = LayoutOptions {
+			max_width: Some(24),
+			align: Align::Center,
+		};
+		let layout = FontMan::layout_text("ABC D", &sheet, &options);
+		assert_eq!(layout.glyphs.last().unwrap().x, 8);
+	}
+
+	#[test]
+	fn glyphs_point_back_at_their_source_chars() {
+		let sheet = FontMan::load(FONTS[0], 0.0).unwrap();
+		let options = LayoutOptions {
+			max_width: Some(16),
+			align: Align::Left,
+		};
+		// The first char is drawn as `?`, and the space at the wrap is dropped.
+		let layout = FontMan::layout_text("\u{1F600}B CD\nE", &sheet, &options);
+		let indices: Vec<usize> = layout.glyphs.iter().map(|glyph| glyph.index).collect();
+		assert_eq!(indices, [0, 1, 3, 4, 6]);
+		assert_eq!(layout.glyphs[0].char.id, '?' as i32);
+	}
+
+	/// A sheet with only `chars`, each `advance` pixels wide.
+	fn sheet_of(chars: &str, advance: i32) -> BMSheet {
+		let mut text = String::from("common lineHeight=10 base=8 scaleW=64 scaleH=64\n");
+		for char in chars.chars() {
+			text.push_str(&format!(
+				"char id={} x=0 y=0 width=4 height=4 xoffset=0 yoffset=0 xadvance={advance}\n",
+				char as u32
+			));
+		}
+		FontMan::parse(text.as_bytes()).unwrap()
+	}
+
+	#[test]
+	fn missing_glyphs_come_from_the_next_font_with_them() {
+		let mut sheet = sheet_of("AB", 5);
+		sheet.fallbacks = vec![sheet_of("BC", 7), sheet_of("CD", 9)];
+		let found = |char| {
+			sheet
+				.glyph(char)
+				.map(|(index, bm_char)| (index, bm_char.x_advance))
+		};
+		assert_eq!(found('A'), Some((0, 5)));
+		assert_eq!(found('B'), Some((0, 5)));
+		assert_eq!(found('C'), Some((1, 7)));
+		assert_eq!(found('D'), Some((2, 9)));
+
+		let layout = FontMan::layout_text("AD", &sheet, &LayoutOptions::default());
+		let sheets: Vec<usize> = layout.glyphs.iter().map(|glyph| glyph.sheet).collect();
+		assert_eq!(sheets, [0, 2]);
+		assert_eq!(layout.width, 5 + 9);
+	}
+
+	#[test]
+	fn single_case_fonts_draw_both_cases() {
+		let lowercase = sheet_of("ab", 5);
+		let (_, bm_char) = lowercase.glyph('A').unwrap();
+		assert_eq!(bm_char.id, 'a' as i32);
+
+		// A fallback with the exact char doesn't win over folding the case.
+		let mut sheet = sheet_of("AB", 5);
+		sheet.fallbacks = vec![sheet_of("ab", 7)];
+		assert_eq!(sheet.glyph('a').map(|(index, _)| index), Some(0));
+		// Chars whose case changes length, like `ß`, aren't folded.
+		assert!(sheet_of("S", 5).glyph('\u{df}').is_none());
+	}
+
+	#[test]
+	fn chars_no_font_has_are_drawn_as_question_marks() {
+		let mut sheet = sheet_of("A", 5);
+		sheet.fallbacks = vec![sheet_of("?", 7)];
+		let (index, bm_char) = sheet.glyph('\u{1F600}').unwrap();
+		assert_eq!((index, bm_char.id), (1, '?' as i32));
+		assert_eq!(FontMan::text_width("A\u{1F600}", &sheet), 5 + 7);
+
+		// Without a `?` either, the char takes no space at all.
+		let sheet = sheet_of("A", 5);
+		assert!(sheet.glyph('\u{1F600}').is_none());
+		let layout = FontMan::layout_text("A\u{1F600}A", &sheet, &LayoutOptions::default());
+		assert_eq!(layout.glyphs.len(), 2);
+		assert_eq!(layout.width, 10);
+	}
+}
@@ -0,0 +1,212 @@
+//! Rasterizes TrueType and OpenType fonts into an in-memory [`BMSheet`], so they
+//! go through the same layout and rendering as bitmap fonts.
+
+use std::path::Path;
+
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont as _};
+
+use super::{BMChar, BMCommon, BMKerning, BMSheet, FontMan, Page};
+
+/// Narrowest the atlas gets, it's widened for glyphs that don't fit.
+const ATLAS_WIDTH: u32 = 512;
+/// Gap between glyphs in the atlas so linear filtering doesn't bleed neighbours in.
+const PADDING: u32 = 2;
+
+/// Chars baked into the atlas, when the font has them.
+const CHAR_RANGES: [std::ops::RangeInclusive<char>; 4] = [
+	' '..='~',
+	'\u{a0}'..='\u{17f}',
+	'\u{2010}'..='\u{203a}',
+	'\u{20ac}'..='\u{20ac}',
+];
+
+pub fn is_ttf(font: &str) -> bool {
+	Path::new(font)
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("ttf") || ext.eq_ignore_ascii_case("otf"))
+}
+
+pub fn load(path: &str, size: f32) -> Result<BMSheet, String> {
+	let data = std::fs::read(path).map_err(|err| format!("failed to read {path}: {err}"))?;
+	let font = FontVec::try_from_vec(data).map_err(|err| format!("{path}: {err}"))?;
+	let font = font.as_scaled(PxScale::from(size));
+
+	let ascent = font.ascent().ceil();
+	let line_height = (font.ascent() - font.descent() + font.line_gap()).ceil() as i32;
+
+	let glyphs: Vec<_> = CHAR_RANGES
+		.iter()
+		.flat_map(|range| range.clone())
+		.map(|char| (char, font.glyph_id(char)))
+		// Glyph 0 is the font's "missing" glyph, leave those to the fallback chain.
+		.filter(|(_, id)| id.0 != 0)
+		.collect();
+
+	let outlines: Vec<_> = glyphs
+		.iter()
+		.map(|&(_, id)| {
+			font.outline_glyph(
+				id.with_scale_and_position(font.scale(), ab_glyph::point(0.0, ascent)),
+			)
+		})
+		.collect();
+	let widest = outlines
+		.iter()
+		.flatten()
+		.map(|outline| outline.px_bounds().width() as u32)
+		.max()
+		.unwrap_or(0);
+	let atlas_width = (widest + PADDING * 2).max(ATLAS_WIDTH).next_power_of_two();
+
+	// Pack glyphs into shelves left to right, top to bottom.
+	let mut chars = Vec::new();
+	let (mut x, mut y, mut shelf_height) = (PADDING, PADDING, 0);
+	for (&(char, id), outline) in glyphs.iter().zip(&outlines) {
+		let (width, height, x_offset, y_offset) = match outline {
+			Some(outline) => {
+				let bounds = outline.px_bounds();
+				(
+					bounds.width() as u32,
+					bounds.height() as u32,
+					bounds.min.x as i32,
+					bounds.min.y as i32,
+				)
+			}
+			None => (0, 0, 0, 0),
+		};
+
+		if x + width + PADDING > atlas_width {
+			x = PADDING;
+			y += shelf_height + PADDING;
+			shelf_height = 0;
+		}
+
+		chars.push(BMChar {
+			id: char as i32,
+			x: x as i32,
+			y: y as i32,
+			width: width as i32,
+			height: height as i32,
+			x_offset,
+			y_offset,
+			x_advance: font.h_advance(id).round() as i32,
+			page: 0,
+		});
+
+		x += width + PADDING;
+		shelf_height = shelf_height.max(height);
+	}
+	let atlas_height = (y + shelf_height + PADDING).next_power_of_two();
+
+	// White glyphs with coverage as alpha, like the bitmap font textures.
+	let mut atlas =
+		image::RgbaImage::from_pixel(atlas_width, atlas_height, image::Rgba([255, 255, 255, 0]));
+	for (bm_char, outline) in chars.iter().zip(&outlines) {
+		let Some(outline) = outline else {
+			continue;
+		};
+		outline.draw(|gx, gy, coverage| {
+			let pixel = atlas.get_pixel_mut(bm_char.x as u32 + gx, bm_char.y as u32 + gy);
+			pixel.0[3] = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+		});
+	}
+
+	// Only printable ASCII gets kerning, every pair of the full set is a lot of lookups.
+	let ascii: Vec<_> = glyphs.iter().filter(|(char, _)| char.is_ascii()).collect();
+	let mut kernings = Vec::new();
+	for &&(first, first_id) in &ascii {
+		for &&(second, second_id) in &ascii {
+			let amount = font.kern(first_id, second_id).round() as i32;
+			if amount != 0 {
+				kernings.push(BMKerning {
+					first: first as i32,
+					second: second as i32,
+					amount,
+				});
+			}
+		}
+	}
+
+	let common = BMCommon {
+		line_height,
+		base: ascent as i32,
+		scale_w: atlas_width as i32,
+		scale_h: atlas_height as i32,
+	};
+	FontMan::build_sheet(common, vec![Page::Image(atlas)], chars, kernings)
+		.map_err(|err| format!("{path}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// DejaVu Sans cut down to ` !,.?AVTWYaeo`, keeping its kerning table.
+	const FONT: &str = concat!(
+		env!("CARGO_MANIFEST_DIR"),
+		"/tests/fixtures/DejaVuSans-subset.ttf"
+	);
+
+	#[test]
+	fn rasterizes_every_char_the_font_has_into_one_page() {
+		let sheet = load(FONT, 32.0).unwrap();
+		let (width, height) = (sheet.common.scale_w as u32, sheet.common.scale_h as u32);
+		assert_eq!(width, ATLAS_WIDTH);
+		assert!(height.is_power_of_two());
+		let [Page::Image(atlas)] = &sheet.pages[..] else {
+			panic!("expected a single rasterized page");
+		};
+		assert_eq!(atlas.dimensions(), (width, height));
+
+		assert_eq!(sheet.char_index.len(), " !,.?AVTWYaeo".len());
+		assert!(!sheet.char_index.contains_key(&'b'));
+		for (char, bm_char) in &sheet.char_index {
+			assert!(bm_char.x_advance > 0, "{char:?}");
+			assert!((bm_char.x + bm_char.width) as u32 <= width, "{char:?}");
+			assert!((bm_char.y + bm_char.height) as u32 <= height, "{char:?}");
+		}
+
+		let a = &sheet.char_index[&'A'];
+		assert!(a.width > 0 && a.height > 0);
+		assert!(a.height <= sheet.common.line_height);
+		let covered = (0..a.height as u32)
+			.flat_map(|y| (0..a.width as u32).map(move |x| (x, y)))
+			.filter(|&(x, y)| atlas.get_pixel(a.x as u32 + x, a.y as u32 + y).0[3] > 0)
+			.count();
+		assert!(covered > 0, "A was drawn empty");
+	}
+
+	#[test]
+	fn spaces_advance_without_a_bitmap() {
+		let sheet = load(FONT, 32.0).unwrap();
+		let space = &sheet.char_index[&' '];
+		assert!(space.x_advance > 0);
+		assert_eq!((space.width, space.height), (0, 0));
+	}
+
+	#[test]
+	fn kerning_pairs_come_through_to_the_sheet() {
+		let sheet = load(FONT, 32.0).unwrap();
+		let data = std::fs::read(FONT).unwrap();
+		let font = FontVec::try_from_vec(data).unwrap();
+		let font = font.as_scaled(PxScale::from(32.0));
+		let kern = font.kern(font.glyph_id('A'), font.glyph_id('V')).round() as i32;
+
+		assert!(kern < 0, "AV should be kerned closer");
+		assert_eq!(sheet.kerning('A' as i32, 'V' as i32), kern);
+		let advance = |char| sheet.char_index[&char].x_advance;
+		assert_eq!(
+			FontMan::text_width("AV", &sheet),
+			advance('A') + advance('V') + kern
+		);
+	}
+
+	#[test]
+	fn only_ttf_and_otf_files_are_rasterized() {
+		assert!(is_ttf("fonts/Sans.TTF"));
+		assert!(is_ttf("Sans.otf"));
+		assert!(!is_ttf("assets/fonts/SpaceMono"));
+		assert!(!is_ttf("ttf"));
+	}
+}
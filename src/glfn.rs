@@ -2,6 +2,7 @@ use gl::types::*;
 use std::ffi::CString;
 use std::fs::File;
 use std::io::Read as _;
+use std::path::Path;
 use std::ptr;
 
 pub fn buffer_data_array(target: GLenum, data: &[u8], usage_hint: GLenum) {
@@ -23,50 +24,61 @@ pub fn buffer_data_array(target: GLenum, data: &[u8], usage_hint: GLenum) {
 	}
 }
 
-pub fn shader(fragment: &str, vertex: &str) -> GLuint {
-	// tracing::info!(
-	// 	"Received shader request.\nFragment: {}\nVertex: {}",
-	// 	fragment,
-	// 	vertex
-	// );
-	let vertex_shader = compile_shader(vertex, gl::VERTEX_SHADER);
-	let fragment_shader = compile_shader(fragment, gl::FRAGMENT_SHADER);
+/// Compiles and links a program, returning the driver's log when that fails.
+pub fn shader(fragment: &str, vertex: &str) -> Result<GLuint, String> {
+	let vertex_shader = compile_shader(vertex, gl::VERTEX_SHADER)?;
+	let fragment_shader = match compile_shader(fragment, gl::FRAGMENT_SHADER) {
+		Ok(shader) => shader,
+		Err(err) => {
+			unsafe { gl::DeleteShader(vertex_shader) };
+			return Err(err);
+		}
+	};
 
 	unsafe {
 		let program = gl::CreateProgram();
 		gl::AttachShader(program, vertex_shader);
 		gl::AttachShader(program, fragment_shader);
 		gl::LinkProgram(program);
+		gl::DeleteShader(vertex_shader);
+		gl::DeleteShader(fragment_shader);
 
 		let mut success: GLint = 0;
 		gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
 		if success == gl::FALSE as GLint {
-			let mut info_log: Vec<u8> = Vec::with_capacity(512);
-			gl::GetProgramInfoLog(
-				program,
-				512,
-				ptr::null_mut(),
-				info_log.as_mut_ptr() as *mut i8,
-			);
-			tracing::error!("ERROR::PROGRAM::LINKING_FAILED\n{:?}", info_log);
+			let log =
+				info_log(|len, buf| gl::GetProgramInfoLog(program, len, ptr::null_mut(), buf));
+			gl::DeleteProgram(program);
+			return Err(format!("failed to link shader: {log}"));
 		}
-		program
+		Ok(program)
 	}
 }
 
-fn load_shader_file(vertex_filename: &str) -> String {
-	let mut file = File::open(vertex_filename).expect("Failed to open shader file.");
+/// Compiles one of the shaders built into the binary. These are expected to
+/// always compile, so failures are only logged and leave program 0 bound.
+pub fn builtin_shader(fragment: &[u8], vertex: &[u8]) -> GLuint {
+	let fragment = std::str::from_utf8(fragment).unwrap();
+	let vertex = std::str::from_utf8(vertex).unwrap();
+	shader(fragment, vertex).unwrap_or_else(|err| {
+		tracing::error!("built-in shader: {err}");
+		0
+	})
+}
+
+pub fn load_shader_file(path: &Path) -> Result<String, String> {
+	let mut file =
+		File::open(path).map_err(|e| format!("failed to open {}: {e}", path.display()))?;
 	let mut contents = String::new();
 	file.read_to_string(&mut contents)
-		.expect("Failed to read shader file.");
-	contents
+		.map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+	Ok(contents)
 }
 
-fn compile_shader(source: &str, shader_type: GLenum) -> GLuint {
+fn compile_shader(source: &str, shader_type: GLenum) -> Result<GLuint, String> {
+	let c_str =
+		CString::new(source).map_err(|_| "shader source contains a NUL byte".to_string())?;
 	let shader = unsafe { gl::CreateShader(shader_type) };
-	let c_str = CString::new(source).expect("Failed to convert source to CString.");
-
-	// tracing::info!("c_str: {:?}", c_str);
 
 	unsafe {
 		gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
@@ -78,19 +90,19 @@ fn compile_shader(source: &str, shader_type: GLenum) -> GLuint {
 		gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
 	}
 	if success == gl::FALSE as GLint {
-		let mut info_log = vec![0; 512]; // Properly initialize the buffer
-		unsafe {
-			gl::GetShaderInfoLog(
-				shader,
-				512,
-				ptr::null_mut(),
-				info_log.as_mut_ptr() as *mut i8,
-			);
-		}
-		let log_message = String::from_utf8_lossy(&info_log);
-		let log_message = log_message.trim_end_matches(char::from(0));
-		tracing::error!("Shader Info Log: {}", log_message);
+		let log =
+			info_log(|len, buf| unsafe { gl::GetShaderInfoLog(shader, len, ptr::null_mut(), buf) });
+		unsafe { gl::DeleteShader(shader) };
+		return Err(format!("failed to compile shader: {log}"));
 	}
 
-	shader
+	Ok(shader)
+}
+
+/// Reads a shader or program info log through `get`.
+fn info_log(get: impl FnOnce(GLsizei, *mut GLchar)) -> String {
+	let mut log = vec![0u8; 1024];
+	get(log.len() as GLsizei, log.as_mut_ptr() as *mut GLchar);
+	let log = String::from_utf8_lossy(&log);
+	log.trim_end_matches(char::from(0)).trim_end().to_string()
 }
@@ -9,6 +9,8 @@ uniform float time;
 
 out vec4 FragColor;
 
+// `body_uv_transform` in src/graphics/geometry.rs does the same on the CPU.
+
 vec2 rotate(vec2 uv, float angle) {
   // algebra formula for rotation by matrix , https://en.wikipedia.org/wiki/Rotation_matrix
   mat2 m = mat2(cos(angle), -sin(angle), sin(angle), cos(angle));
@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 TexCoord;
+in vec4 Color;
+uniform sampler2D texture1;
+
+out vec4 FragColor;
+
+void main() {
+  vec4 texColor = texture(texture1, TexCoord) * Color;
+
+  if (texColor.a < 0.1) {
+    discard;
+  }
+
+  FragColor = texColor;
+}
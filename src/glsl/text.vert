@@ -0,0 +1,37 @@
+#version 330 core
+#extension GL_ARB_separate_shader_objects : enable
+
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec2 aTexCoord;
+layout (location = 2) in vec4 aColor;
+// x: shake, y: wave, z: glyph number
+layout (location = 3) in vec3 aEffect;
+
+uniform float time;
+// Size of one pixel in clip space.
+uniform vec2 pixelSize;
+
+out vec2 TexCoord;
+out vec4 Color;
+
+// `glyph_offset` in src/graphics/geometry.rs does the same on the CPU.
+float random(vec2 seed) {
+  return fract(sin(dot(seed, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+  float glyph = aEffect.z;
+  vec2 offset = vec2(0.0);
+
+  if (aEffect.x > 0.5) {
+    float step = floor(time * 20.0);
+    offset += (vec2(random(vec2(glyph, step)), random(vec2(step, glyph))) - 0.5) * 2.0;
+  }
+  if (aEffect.y > 0.5) {
+    offset.y += sin(time * 6.0 - glyph * 0.6) * 2.0;
+  }
+
+  gl_Position = vec4(aPos.xy + offset * pixelSize, aPos.z, 1.0);
+  TexCoord = aTexCoord.xy;
+  Color = aColor;
+}
@@ -1,6 +1,7 @@
 use super::{
 	super::{
-		font_manager::FontMan,
+		config,
+		font_manager::{markup::StyledText, Align, BMSheet, FontMan, LayoutOptions, TextLayout},
 		vec2::Vec2,
 		window::{Window, Windowed},
 	},
@@ -10,34 +11,116 @@ use glfw::Context as _;
 
 pub struct Dialog {
 	text: render::Text,
-	parent: Option<Box<Dialog>>,
+	parent: Option<(Vec2, Vec2)>,
 	parent_relative_pos: Vec2,
-	timer: f64,
 	window: Window,
 	window_size: Vec2,
+	timeline: Timeline,
+}
+
+/// When a bubble's glyphs show up, when talk sounds play and when it closes.
+struct Timeline {
+	/// Index in the text of every glyph, in the order they're revealed.
+	glyphs: Vec<usize>,
+	settings: config::Dialog,
+	/// How many glyphs are shown so far.
+	revealed: usize,
+	reveal_timer: f64,
+	/// Talk sounds due since the owner last asked, see [`Dialog::take_talk_sounds`].
+	talk_sounds: usize,
+	/// Seconds left before the bubble closes, counted once the text is fully revealed.
+	timer: f64,
+}
+
+impl Timeline {
+	/// Revealing one of these is followed by a pause.
+	const PUNCTUATION: [char; 8] = ['.', ',', '!', '?', ';', ':', '\u{2026}', '\u{2014}'];
+
+	fn new(
+		text: &StyledText,
+		layout: &TextLayout,
+		duration: f64,
+		settings: &config::Dialog,
+	) -> Self {
+		let glyphs: Vec<usize> = layout.glyphs.iter().map(|glyph| glyph.index).collect();
+		// Without a reveal everything shows up at once, with a single talk sound.
+		let (revealed, talk_sounds) = if settings.reveal_speed > 0.0 {
+			(0, 0)
+		} else {
+			(glyphs.len(), 1)
+		};
+		let reveal_timer = glyphs
+			.first()
+			.map_or(0.0, |&first| text.pauses(0..first + 1));
+		Self {
+			glyphs,
+			settings: settings.clone(),
+			revealed,
+			reveal_timer,
+			talk_sounds,
+			timer: duration,
+		}
+	}
+
+	fn revealing(&self) -> bool {
+		self.revealed < self.glyphs.len()
+	}
+
+	fn finished(&self) -> bool {
+		self.timer <= 0.0
+	}
+
+	fn update(&mut self, text: &StyledText, dt: f64) {
+		if !self.revealing() {
+			self.timer -= dt;
+			return;
+		}
+
+		let interval = self.settings.talk_sound_interval.max(1) as usize;
+		self.reveal_timer -= dt;
+		while self.reveal_timer <= 0.0 && self.revealing() {
+			if self.revealed.is_multiple_of(interval) {
+				self.talk_sounds += 1;
+			}
+			let index = self.glyphs[self.revealed];
+			self.revealed += 1;
+
+			self.reveal_timer += 1.0 / self.settings.reveal_speed;
+			// Check what was written, the glyph may be a replacement the font had instead.
+			let char = text.char_at(index);
+			if char.is_some_and(|char| Self::PUNCTUATION.contains(&char)) {
+				self.reveal_timer += self.settings.punctuation_pause;
+			}
+			if let Some(&next) = self.glyphs.get(self.revealed) {
+				self.reveal_timer += text.pauses(index + 1..next + 1);
+			}
+		}
+	}
 }
 
 impl Dialog {
 	pub const DEFAULT_DURATION: f64 = 6.0;
 	const PADDING: f64 = 10.0;
+	/// Widest a line of text gets before it wraps.
+	const MAX_TEXT_WIDTH: i32 = 320;
 
+	/// Creates a speech bubble centered on `position`. `parent` is the position
+	/// and size of the window the bubble should follow, if any. `text` can use
+	/// [markup](StyledText::parse), and is revealed over time as `settings` say,
+	/// `duration` starts counting after.
 	pub fn new(
+		glfw: &mut glfw::Glfw,
 		text: &str,
-		font: &str,
+		sheet: BMSheet,
 		position: Vec2,
 		duration: f64,
-		parent: Option<Box<Dialog>>,
+		parent: Option<(Vec2, Vec2)>,
+		settings: &config::Dialog,
 	) -> Self {
-		let sheet = FontMan::parse_bm(&std::fs::read_to_string(format!("{}.fnt", font)).unwrap());
-
-		let (text_width, text_height, _) = FontMan::position_text(text, &sheet);
-
-		let window_size = Vec2::new(
-			text_width as f64 + Self::PADDING * 2.0,
-			text_height as f64 + Self::PADDING * 2.0,
-		);
+		let (text, layout, window_size) = Self::layout(text, &sheet);
 
 		let mut window = Window::new(
+			glfw,
 			window_size.x as u32,
 			window_size.y as u32,
 			"!!__FUNFRIEND__!! > CHATTER",
@@ -48,46 +131,73 @@ impl Dialog {
 			(position.y - window_size.y / 2.0) as i32,
 		);
 
-		let renderer = render::Text::new(
-			text.to_string(),
-			font.to_string(),
-			sheet,
-			window_size.x as i32,
-			window_size.x as i32,
-		);
-
 		let mut parent_relative_pos = Vec2::zero();
-		if let Some(ref p) = parent {
-			let parent_window_pos = Vec2::new_t(p.window.handle.get_pos());
-			let parent_window_size = Vec2::new(parent_window_pos.x, parent_window_pos.y);
-			parent_relative_pos = position - (parent_window_size / 2.0);
+		if let Some((parent_pos, parent_size)) = parent {
+			parent_relative_pos = position - (parent_pos + parent_size / 2.0);
 		}
 
-		window.handle.make_current();
-		gl::load_with(|s| window.glfw.get_proc_address_raw(s) as *const _);
+		window.make_current();
 
 		unsafe {
 			gl::Enable(gl::BLEND);
 			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 		}
 
+		let mut renderer = render::Text::new(
+			text,
+			&sheet,
+			layout,
+			window_size.x as i32,
+			window_size.y as i32,
+			settings.shader.as_deref(),
+		);
+
+		let timeline = Timeline::new(&renderer.text, &renderer.layout, duration, settings);
+		renderer.set_visible(timeline.revealed);
+
 		Self {
 			text: renderer,
 			parent,
 			parent_relative_pos,
 			window_size,
-			timer: duration,
 			window,
+			timeline,
 		}
 	}
 
+	/// Parses and lays out `text` the way a bubble shows it, returning the size of
+	/// the bubble's window along with it.
+	pub fn layout(text: &str, sheet: &BMSheet) -> (StyledText, TextLayout, Vec2) {
+		let text = StyledText::parse(text);
+		let layout = FontMan::layout_text(
+			&text.text(),
+			sheet,
+			&LayoutOptions {
+				max_width: Some(Self::MAX_TEXT_WIDTH),
+				align: Align::Center,
+			},
+		);
+
+		let window_size = Vec2::new(
+			layout.width as f64 + Self::PADDING * 2.0,
+			layout.height as f64 + Self::PADDING * 2.0,
+		);
+		(text, layout, window_size)
+	}
+
+	/// How many talk sounds should play for the text revealed since the last call.
+	pub fn take_talk_sounds(&mut self) -> usize {
+		std::mem::take(&mut self.timeline.talk_sounds)
+	}
+
+	pub fn set_parent(&mut self, position: Vec2, size: Vec2) {
+		self.parent = Some((position, size));
+	}
+
 	pub fn update_pos(&mut self) {
-		if let Some(ref p) = self.parent {
-			let parent_window_pos = Vec2::new_t(p.window.handle.get_pos());
-			let parent_window_size = Vec2::new(parent_window_pos.x, parent_window_pos.y);
+		if let Some((parent_pos, parent_size)) = self.parent {
 			let new_pos =
-				(parent_window_pos + parent_window_size / 2.0 + self.parent_relative_pos.x
-					- self.window_size / 2.0);
+				parent_pos + parent_size / 2.0 + self.parent_relative_pos - self.window_size / 2.0;
 			self.window
 				.handle
 				.set_pos(new_pos.x as i32, new_pos.y as i32);
@@ -95,26 +205,29 @@ impl Dialog {
 	}
 
 	pub fn render(&mut self, dt: f64) {
-		self.window.handle.make_current();
-		gl::load_with(|s| self.window.glfw.get_proc_address_raw(s) as *const _);
+		self.window.make_current();
 		unsafe {
 			gl::ClearColor(0.0, 0.0, 0.0, 1.0);
 			gl::Clear(gl::COLOR_BUFFER_BIT);
 		}
+		self.text.update(dt);
 		self.text.render();
+		self.window.handle.swap_buffers();
 	}
 
-	pub fn bump(&mut self) {
-		self.parent_relative_pos.y -= self.window_size.y + 10.0;
+	/// Moves the bubble up to make room for a new one `height` pixels tall below it.
+	pub fn bump(&mut self, height: f64) {
+		self.parent_relative_pos.y -= height + 10.0;
 		self.update_pos();
 	}
 }
 
 impl Windowed for Dialog {
 	fn update(&mut self, dt: f64) {
-		tracing::info!("text timer: {}", self.timer);
-		self.timer -= dt;
-		if self.timer <= 0.0 {
+		tracing::trace!("text timer: {}", self.timeline.timer);
+		self.timeline.update(&self.text.text, dt);
+		self.text.set_visible(self.timeline.revealed);
+		if self.timeline.finished() {
 			self.window.handle.set_should_close(true);
 		}
 		self.update_pos();
@@ -133,3 +246,127 @@ impl Windowed for Dialog {
 		&mut self.window
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The timeline of a bubble saying `source` in a font with every char it uses.
+	fn start(source: &str, settings: &config::Dialog) -> (StyledText, Timeline) {
+		let sheet = FontMan::load("assets/fonts/SpaceMono", 0.0).unwrap();
+		let text = StyledText::parse(source);
+		let layout = FontMan::layout_text(&text.text(), &sheet, &LayoutOptions::default());
+		let timeline = Timeline::new(&text, &layout, 1.0, settings);
+		(text, timeline)
+	}
+
+	fn settings() -> config::Dialog {
+		config::Dialog {
+			reveal_speed: 4.0,
+			punctuation_pause: 0.5,
+			talk_sound_interval: 2,
+			..Default::default()
+		}
+	}
+
+	/// Glyphs revealed after each of `steps` updates of `dt` seconds.
+	fn reveal(text: &StyledText, timeline: &mut Timeline, dt: f64, steps: usize) -> Vec<usize> {
+		(0..steps)
+			.map(|_| {
+				timeline.update(text, dt);
+				timeline.revealed
+			})
+			.collect()
+	}
+
+	#[test]
+	fn reveals_chars_at_the_reveal_speed() {
+		// The first char shows up right away, the next every quarter second.
+		let (text, mut timeline) = start("ABCD", &settings());
+		assert_eq!(timeline.revealed, 0);
+		assert_eq!(reveal(&text, &mut timeline, 0.25, 3), [2, 3, 4]);
+
+		// A long frame reveals several at once.
+		let (text, mut timeline) = start("ABCD", &settings());
+		assert_eq!(reveal(&text, &mut timeline, 0.5, 1), [3]);
+	}
+
+	#[test]
+	fn waits_after_punctuation_and_pauses() {
+		// `B` waits for the punctuation pause after `.`, `C` for the markup pause.
+		let (text, mut timeline) = start("A.B[pause=0.25]C", &settings());
+		let revealed = reveal(&text, &mut timeline, 0.25, 6);
+		assert_eq!(revealed, [2, 2, 2, 3, 3, 4]);
+	}
+
+	#[test]
+	fn talk_sounds_play_every_interval_chars() {
+		let (text, mut timeline) = start("ABCDE", &settings());
+		let mut sounds = Vec::new();
+		for _ in 0..4 {
+			timeline.update(&text, 0.25);
+			sounds.push(std::mem::take(&mut timeline.talk_sounds));
+		}
+		assert_eq!(sounds, [1, 1, 0, 1]);
+
+		let instant = config::Dialog {
+			reveal_speed: 0.0,
+			..settings()
+		};
+		let (_, timeline) = start("ABCDE", &instant);
+		assert!(!timeline.revealing());
+		assert_eq!(timeline.talk_sounds, 1);
+	}
+
+	#[test]
+	fn closes_only_once_the_text_is_revealed() {
+		let (text, mut timeline) = start("ABCD", &settings());
+		reveal(&text, &mut timeline, 0.5, 2);
+		// A second went by, but all of it was spent revealing.
+		assert!(!timeline.revealing());
+		assert_eq!(timeline.timer, 1.0);
+
+		timeline.update(&text, 0.6);
+		assert!(!timeline.finished());
+		timeline.update(&text, 0.6);
+		assert!(timeline.finished());
+	}
+
+	#[test]
+	fn lays_out_ttf_fonts_like_bitmap_ones() {
+		let font = concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/tests/fixtures/DejaVuSans-subset.ttf"
+		);
+		let sheet = FontMan::load(font, 24.0).unwrap();
+		let (text, layout, window_size) = Dialog::layout("[wave]AVA[/wave] Yo!", &sheet);
+		assert_eq!(text.text(), "AVA Yo!");
+		assert_eq!(layout.height, sheet.common.line_height);
+		assert_eq!(
+			(window_size.x, window_size.y),
+			(
+				layout.width as f64 + Dialog::PADDING * 2.0,
+				layout.height as f64 + Dialog::PADDING * 2.0
+			)
+		);
+
+		// Every char gets a glyph, and `V` is kerned closer to the `A` before it.
+		let indices: Vec<_> = layout.glyphs.iter().map(|glyph| glyph.index).collect();
+		assert_eq!(indices, [0, 1, 2, 3, 4, 5, 6]);
+		let pen = |i: usize| layout.glyphs[i].x - layout.glyphs[i].char.x_offset;
+		let a = &sheet.char_index[&'A'];
+		let kern = sheet.kerning('A' as i32, 'V' as i32);
+		assert!(kern < 0);
+		assert_eq!(pen(1) - pen(0), a.x_advance + kern);
+
+		// Long text wraps at the bubble's width.
+		let (_, wrapped, _) = Dialog::layout(&"AVA Yo! ".repeat(20), &sheet);
+		assert!(wrapped.width <= Dialog::MAX_TEXT_WIDTH);
+		assert!(wrapped.height > sheet.common.line_height);
+
+		// And it's revealed like any other text.
+		let mut timeline = Timeline::new(&text, &layout, 1.0, &settings());
+		timeline.update(&text, 0.25);
+		assert_eq!(timeline.revealed, 2);
+	}
+}
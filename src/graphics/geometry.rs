@@ -0,0 +1,227 @@
+//! Vertex math shared by the GL renderers and the software renderer, and the
+//! software renderer's copies of what the built-in shaders compute, so snapshots
+//! check the numbers the windows draw with.
+
+use std::f32::consts::PI;
+
+use super::super::font_manager::{markup::StyledText, BMSheet, FontMan, TextLayout};
+
+/// A quad covering the whole viewport, as position xyz and texture coordinates uv.
+#[rustfmt::skip]
+pub const QUAD_VERTICES: [f32; 20] = [
+	1.0, 1.0, 0.0, 1.0, 1.0, // top right
+	1.0, -1.0, 0.0, 1.0, 0.0, // bottom right
+	-1.0, -1.0, 0.0, 0.0, 0.0, // bottom left
+	-1.0, 1.0, 0.0, 0.0, 1.0, // top left
+];
+pub const QUAD_INDICES: [u32; 6] = [0, 1, 3, 1, 2, 3];
+
+/// Texture coordinates [`QUAD_VERTICES`] interpolate to at the center of pixel
+/// `(x, y)` of a `width` by `height` viewport, counting rows from the top.
+pub fn window_uv(x: u32, y: u32, width: u32, height: u32) -> (f32, f32) {
+	let u = (x as f32 + 0.5) / width as f32;
+	let v = 1.0 - (y as f32 + 0.5) / height as f32;
+	(u, v)
+}
+
+/// A 2D affine transform, `[a, b, c, d, e, f]` mapping `(u, v)` to
+/// `(a u + b v + c, d u + e v + f)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine([f32; 6]);
+
+impl Affine {
+	const IDENTITY: Self = Self([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+
+	/// Applies `next` after `self`.
+	fn then(self, next: Self) -> Self {
+		let [a, b, c, d, e, f] = self.0;
+		let [na, nb, nc, nd, ne, nf] = next.0;
+		Self([
+			na * a + nb * d,
+			na * b + nb * e,
+			na * c + nb * f + nc,
+			nd * a + ne * d,
+			nd * b + ne * e,
+			nd * c + ne * f + nf,
+		])
+	}
+
+	fn translate(self, u: f32, v: f32) -> Self {
+		self.then(Self([1.0, 0.0, u, 0.0, 1.0, v]))
+	}
+
+	fn scale(self, u: f32, v: f32) -> Self {
+		self.then(Self([u, 0.0, 0.0, 0.0, v, 0.0]))
+	}
+
+	fn rotate(self, angle: f32) -> Self {
+		let (sin, cos) = angle.sin_cos();
+		self.then(Self([cos, sin, 0.0, -sin, cos, 0.0]))
+	}
+
+	pub fn apply(&self, u: f32, v: f32) -> (f32, f32) {
+		let [a, b, c, d, e, f] = self.0;
+		(a * u + b * v + c, d * u + e * v + f)
+	}
+}
+
+/// Where `funfriend.frag` samples the body texture for window texture coordinates:
+/// the buddy bouncing and swaying `time` seconds in, scaled down from a `resolution`
+/// sized window to `size`, and flipped to image row order.
+pub fn body_uv_transform(time: f32, size: [f32; 2], resolution: [f32; 2]) -> Affine {
+	let bounce = (time * PI).sin();
+	let sway = (time * 0.5 * PI).sin();
+
+	Affine::IDENTITY
+		.translate(-0.5, -0.5)
+		.translate(0.0, bounce * 0.05)
+		// Turning around the y axis only squashes horizontally.
+		.scale((bounce * (20.0 / 360.0) * PI * 2.0).cos(), 1.0)
+		.translate(sway * 0.05, 0.0)
+		.rotate(sway * (5.0 / 360.0) * PI * 2.0)
+		.scale(resolution[0] / size[0], resolution[1] / size[1])
+		.translate(0.5, 0.5)
+		.then(Affine([1.0, 0.0, 0.0, 0.0, -1.0, 1.0]))
+}
+
+/// Pixel offset `text.vert` moves a glyph by for the shake and wave effects, with
+/// y pointing up. `glyph` is its position in the text.
+pub fn glyph_offset(shake: bool, wave: bool, glyph: f32, time: f32) -> (f32, f32) {
+	fn random(seed: [f32; 2]) -> f32 {
+		((seed[0] * 12.9898 + seed[1] * 78.233).sin() * 43758.547).rem_euclid(1.0)
+	}
+
+	let (mut x, mut y) = (0.0, 0.0);
+	if shake {
+		let step = (time * 20.0).floor();
+		x += (random([glyph, step]) - 0.5) * 2.0;
+		y += (random([step, glyph]) - 0.5) * 2.0;
+	}
+	if wave {
+		y += (time * 6.0 - glyph * 0.6).sin() * 2.0;
+	}
+	(x, y)
+}
+
+/// One glyph of laid out text, in pixels of its viewport with y pointing up.
+#[derive(Debug, Clone)]
+pub struct GlyphQuad {
+	/// Bottom-left corner before effects.
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+	/// Texture coordinates `(x, y, w, h)` on its page, y counting rows from the top.
+	pub uv: (f32, f32, f32, f32),
+	pub color: [f32; 4],
+	pub shake: bool,
+	pub wave: bool,
+	/// Index of the sheet in the fallback chain and page in that sheet.
+	pub sheet: usize,
+	pub page: usize,
+}
+
+impl GlyphQuad {
+	/// Bottom-left corner `time` seconds in, with `glyph` being its position in the text.
+	pub fn position(&self, glyph: usize, time: f32) -> (f32, f32) {
+		let (dx, dy) = glyph_offset(self.shake, self.wave, glyph as f32, time);
+		(self.x + dx, self.y + dy)
+	}
+}
+
+/// Quads for every glyph in `layout` centered in a `width` by `height` viewport,
+/// in layout order so any prefix of the text can be drawn on its own.
+pub fn glyph_quads(
+	layout: &TextLayout,
+	sheet: &BMSheet,
+	styles: &StyledText,
+	width: i32,
+	height: i32,
+) -> Vec<GlyphQuad> {
+	let offset_x = width / 2 - layout.width / 2;
+	let offset_y = height / 2 - layout.height / 2;
+	let sheets: Vec<&BMSheet> = sheet.chain().collect();
+
+	layout
+		.glyphs
+		.iter()
+		.map(|glyph| {
+			let style = styles.style_at(glyph.index);
+			GlyphQuad {
+				x: (glyph.x + offset_x) as f32,
+				y: (glyph.y + offset_y) as f32,
+				width: glyph.char.width as f32,
+				height: glyph.char.height as f32,
+				uv: FontMan::get_letter_crop(&glyph.char, sheets[glyph.sheet]),
+				color: style.color,
+				shake: style.shake,
+				wave: style.wave,
+				sheet: glyph.sheet,
+				page: glyph.char.page as usize,
+			}
+		})
+		.collect()
+}
+
+/// Floats per vertex in [`text_vertices`]: position xyz, uv, color rgba and the
+/// effects `text.vert` animates.
+pub const TEXT_VERTEX_SIZE: usize = 12;
+
+/// Clip space vertices of `quads`, four per glyph, for `text.vert`. Effects are
+/// given as shake, wave and the glyph's position in the text.
+pub fn text_vertices(quads: &[GlyphQuad], width: i32, height: i32) -> Vec<f32> {
+	let mut vertices = Vec::with_capacity(quads.len() * 4 * TEXT_VERTEX_SIZE);
+	for (i, quad) in quads.iter().enumerate() {
+		let pos_x = quad.x / width as f32 * 2.0 - 1.0;
+		let pos_y = quad.y / height as f32 * 2.0 - 1.0;
+		let pos_w = quad.width / width as f32 * 2.0;
+		let pos_h = quad.height / height as f32 * 2.0;
+		let (u, v, uw, vh) = quad.uv;
+		let effects = [quad.shake as u8 as f32, quad.wave as u8 as f32, i as f32];
+
+		let corners = [
+			[pos_x + pos_w, pos_y + pos_h, 0.0, u + uw, v],
+			[pos_x + pos_w, pos_y, 0.0, u + uw, v + vh],
+			[pos_x, pos_y, 0.0, u, v + vh],
+			[pos_x, pos_y + pos_h, 0.0, u, v],
+		];
+		for corner in corners {
+			vertices.extend_from_slice(&corner);
+			vertices.extend_from_slice(&quad.color);
+			vertices.extend_from_slice(&effects);
+		}
+	}
+	vertices
+}
+
+/// Two triangles per glyph, matching [`text_vertices`].
+pub fn text_indices(glyphs: usize) -> Vec<u32> {
+	(0..glyphs as u32)
+		.flat_map(|i| [0, 1, 3, 1, 2, 3].map(|corner| corner + i * 4))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn body_at_rest_fills_the_window_upside_down() {
+		let transform = body_uv_transform(0.0, [100.0, 100.0], [100.0, 100.0]);
+		assert_eq!(transform.apply(0.25, 0.75), (0.25, 0.25));
+	}
+
+	#[test]
+	fn body_is_scaled_down_around_the_center() {
+		let transform = body_uv_transform(0.0, [50.0, 100.0], [100.0, 100.0]);
+		let (u, v) = transform.apply(0.25, 0.5);
+		assert!((u - 0.0).abs() < 1e-6 && (v - 0.5).abs() < 1e-6, "{u}, {v}");
+	}
+
+	#[test]
+	fn glyphs_without_effects_stay_put() {
+		assert_eq!(glyph_offset(false, false, 3.0, 1.7), (0.0, 0.0));
+		let (x, y) = glyph_offset(true, false, 3.0, 1.7);
+		assert!(x.abs() <= 1.0 && y.abs() <= 1.0);
+	}
+}
@@ -1,5 +1,8 @@
 pub mod dialog;
+pub mod geometry;
 pub mod render;
+pub mod shader;
+pub mod software;
 
 pub trait Drawable {
 	fn update(&mut self, dt: f64);
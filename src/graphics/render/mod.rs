@@ -1,5 +1,3 @@
-pub mod buddy;
 pub mod text;
 
-pub use buddy::Buddy;
 pub use text::Text;
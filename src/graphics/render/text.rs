@@ -1,50 +1,82 @@
 use gl::types::*;
 use std::ffi::CString;
+use std::path::Path;
 
-use super::super::super::{font_manager::*, glfn, texture::load_texture};
+use super::super::{
+	super::{
+		font_manager::{markup::StyledText, *},
+		texture::{load_texture, upload_texture},
+		TEXT_FRAG, TEXT_VERT,
+	},
+	geometry::{self, GlyphQuad},
+	shader,
+};
 
 pub struct Text {
-	pub text: String,
-	pub font: String,
-	pub sheet: BMSheet,
+	pub text: StyledText,
+	pub layout: TextLayout,
 	pub width: i32,
 	pub height: i32,
 
 	pub shader_program: GLuint,
 	pub vertex_array: GLuint,
 	pub vertex_buffer: GLuint,
-	pub font_texture: GLuint,
+	/// One texture for every sheet and page the text uses.
+	pub textures: Vec<GLuint>,
+	/// Runs of consecutive glyphs in the mesh and the texture to draw them with.
+	pub draws: Vec<(GLuint, usize)>,
+	/// How many glyphs from the start of the text are drawn.
+	pub visible: usize,
+	/// Seconds since the text was created, drives the shake and wave effects.
+	pub time: f64,
 }
 
 impl Text {
-	pub fn new(text: String, font: String, sheet: BMSheet, width: i32, height: i32) -> Self {
-		let nop_frag = std::str::from_utf8(crate::NOP_FRAG).unwrap();
-		let nop_vert = std::str::from_utf8(crate::NOP_VERT).unwrap();
-		let shader_program = glfn::shader(nop_frag, nop_vert);
-		let (vertex_array, vertex_buffer) = Self::init_buffers(&text, &sheet, width, height);
-		let font_texture = Self::init_textures(&font);
+	/// Draws `layout` centered in a `width` by `height` viewport, styled by `text`,
+	/// with the fragment shader file at `shader` instead of the built-in one if given.
+	pub fn new(
+		text: StyledText,
+		sheet: &BMSheet,
+		layout: TextLayout,
+		width: i32,
+		height: i32,
+		shader: Option<&Path>,
+	) -> Self {
+		let shader_program = shader::load(shader, TEXT_FRAG, TEXT_VERT);
+		let quads = geometry::glyph_quads(&layout, sheet, &text, width, height);
+		let (vertex_array, vertex_buffer) = Self::init_buffers(&quads, width, height);
+		let (textures, draws) = Self::init_draws(&layout, sheet);
+		let visible = layout.glyphs.len();
 		Self {
 			text,
-			font,
-			sheet,
+			layout,
 			width,
 			height,
 			shader_program,
 			vertex_array,
 			vertex_buffer,
-			font_texture,
+			textures,
+			draws,
+			visible,
+			time: 0.0,
 		}
 	}
 
+	pub fn update(&mut self, dt: f64) {
+		self.time += dt;
+	}
+
+	/// Limits drawing to the first `count` glyphs, for revealing text over time.
+	pub fn set_visible(&mut self, count: usize) {
+		self.visible = count.min(self.layout.glyphs.len());
+	}
+
 	//noinspection RsCStringPointer
 	pub fn render(&self) {
 		unsafe {
 			gl::Enable(gl::BLEND);
 			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
-			gl::ActiveTexture(gl::TEXTURE0);
-			gl::BindTexture(gl::TEXTURE_2D, self.font_texture);
-
 			gl::UseProgram(self.shader_program);
 			gl::Uniform1i(
 				gl::GetUniformLocation(
@@ -53,41 +85,93 @@ impl Text {
 				),
 				0,
 			);
+			gl::Uniform1f(
+				gl::GetUniformLocation(self.shader_program, CString::new("time").unwrap().as_ptr()),
+				self.time as f32,
+			);
+			gl::Uniform2f(
+				gl::GetUniformLocation(
+					self.shader_program,
+					CString::new("pixelSize").unwrap().as_ptr(),
+				),
+				2.0 / self.width as f32,
+				2.0 / self.height as f32,
+			);
 
 			gl::BindVertexArray(self.vertex_array);
-			gl::DrawElements(
-				gl::TRIANGLES,
-				6 * self.text.len() as i32,
-				gl::UNSIGNED_INT,
-				std::ptr::null(),
-			);
+			gl::ActiveTexture(gl::TEXTURE0);
+
+			let mut start = 0;
+			for &(texture, count) in &self.draws {
+				let count = count.min(self.visible - start);
+				if count == 0 {
+					break;
+				}
+				gl::BindTexture(gl::TEXTURE_2D, texture);
+				gl::DrawElements(
+					gl::TRIANGLES,
+					6 * count as i32,
+					gl::UNSIGNED_INT,
+					(6 * start * std::mem::size_of::<u32>()) as *const _,
+				);
+				start += count;
+			}
 		}
 	}
 
-	fn init_textures(font: &str) -> GLuint {
-		let texture_data = load_texture(
-			&format!("{}.png", font),
-			Some([
-				(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER),
-				(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER),
-				(gl::TEXTURE_MIN_FILTER, gl::LINEAR),
-				(gl::TEXTURE_MAG_FILTER, gl::LINEAR),
-			]),
-		);
-		texture_data.unwrap().tex
+	/// Loads every page the text uses once, and splits the mesh into runs of
+	/// glyphs that share a page.
+	fn init_draws(layout: &TextLayout, sheet: &BMSheet) -> (Vec<GLuint>, Vec<(GLuint, usize)>) {
+		let mut pages: Vec<(usize, i32)> = Vec::new();
+		let mut textures = Vec::new();
+		let mut draws: Vec<(GLuint, usize)> = Vec::new();
+
+		for glyph in &layout.glyphs {
+			let page = (glyph.sheet, glyph.char.page);
+			let texture = match pages.iter().position(|&known| known == page) {
+				Some(i) => textures[i],
+				None => {
+					let glyph_sheet = sheet.chain().nth(glyph.sheet).unwrap();
+					let texture = Self::init_texture(&glyph_sheet.pages[page.1 as usize]);
+					pages.push(page);
+					textures.push(texture);
+					texture
+				}
+			};
+
+			match draws.last_mut() {
+				Some((last, count)) if *last == texture => *count += 1,
+				_ => draws.push((texture, 1)),
+			}
+		}
+
+		(textures, draws)
 	}
 
-	fn init_buffers(text: &str, sheet: &BMSheet, width: i32, height: i32) -> (GLuint, GLuint) {
-		let text_width = FontMan::text_width(text, sheet);
-		let text_height = sheet.common.line_height;
-		let (vertices, indices) = FontMan::get_text_mesh(
-			text,
-			sheet,
-			width / 2 - text_width / 2,
-			height / 2 - text_height / 2,
-			width,
-			height,
-		);
+	fn init_texture(page: &Page) -> GLuint {
+		let params = Some([
+			(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER),
+			(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER),
+			(gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+			(gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+		]);
+		match page {
+			// Pages are checked when the font loads, this only fails if one went missing since.
+			Page::File(path) => load_texture(path, params).map_or_else(
+				|err| {
+					tracing::error!("{path}: {err}");
+					0
+				},
+				|texture| texture.tex,
+			),
+			Page::Image(img) => upload_texture(img, params).tex,
+		}
+	}
+
+	fn init_buffers(quads: &[GlyphQuad], width: i32, height: i32) -> (GLuint, GLuint) {
+		let vertices = geometry::text_vertices(quads, width, height);
+		let indices = geometry::text_indices(quads.len());
+		let stride = (geometry::TEXT_VERTEX_SIZE * std::mem::size_of::<f32>()) as GLsizei;
 
 		let mut vertex_array: GLuint = 0;
 		let mut vertex_buffer: GLuint = 0;
@@ -114,24 +198,35 @@ impl Text {
 				gl::STATIC_DRAW,
 			);
 
-			gl::VertexAttribPointer(
-				0,
-				3,
-				gl::FLOAT,
-				gl::FALSE,
-				(5 * std::mem::size_of::<f32>()) as GLsizei,
-				std::ptr::null(),
-			);
+			gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
 			gl::EnableVertexAttribArray(0);
 			gl::VertexAttribPointer(
 				1,
 				2,
 				gl::FLOAT,
 				gl::FALSE,
-				(5 * std::mem::size_of::<f32>()) as GLsizei,
+				stride,
 				(3 * std::mem::size_of::<f32>()) as *const _,
 			);
 			gl::EnableVertexAttribArray(1);
+			gl::VertexAttribPointer(
+				2,
+				4,
+				gl::FLOAT,
+				gl::FALSE,
+				stride,
+				(5 * std::mem::size_of::<f32>()) as *const _,
+			);
+			gl::EnableVertexAttribArray(2);
+			gl::VertexAttribPointer(
+				3,
+				3,
+				gl::FLOAT,
+				gl::FALSE,
+				stride,
+				(9 * std::mem::size_of::<f32>()) as *const _,
+			);
+			gl::EnableVertexAttribArray(3);
 		}
 		(vertex_array, vertex_buffer)
 	}
@@ -140,6 +235,8 @@ impl Text {
 		unsafe {
 			gl::DeleteVertexArrays(1, &self.vertex_array);
 			gl::DeleteBuffers(1, &self.vertex_buffer);
+			gl::DeleteTextures(self.textures.len() as GLsizei, self.textures.as_ptr());
+			gl::DeleteProgram(self.shader_program);
 		}
 	}
 }
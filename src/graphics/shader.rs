@@ -0,0 +1,165 @@
+//! Fragment shaders that can be swapped for user files, and are recompiled when
+//! those files change.
+//!
+//! Text shaders aren't watched: every speech bubble compiles its own with [`load`],
+//! so an edit shows up in the next bubble, and a broken one gets the built-in shader
+//! rather than the last one that worked.
+
+use std::{
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use gl::types::*;
+
+use super::super::glfn;
+
+/// Compiles the fragment shader at `path`, or the built-in `fragment` when there
+/// is no path or the file doesn't compile.
+pub fn load(path: Option<&Path>, fragment: &[u8], vertex: &[u8]) -> GLuint {
+	if let Some(path) = path {
+		match compile_file(path, vertex) {
+			Ok(program) => return program,
+			Err(err) => tracing::error!("{err}, using the built-in shader"),
+		}
+	}
+	glfn::builtin_shader(fragment, vertex)
+}
+
+fn compile_file(path: &Path, vertex: &[u8]) -> Result<GLuint, String> {
+	let fragment = glfn::load_shader_file(path)?;
+	let vertex = std::str::from_utf8(vertex).unwrap();
+	glfn::shader(&fragment, vertex).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// A program whose fragment shader comes from a file, if one is given, and is
+/// reloaded when the file's modification time changes.
+pub struct ShaderProgram {
+	pub program: GLuint,
+	watch: Option<FileWatch>,
+	vertex: &'static [u8],
+}
+
+impl ShaderProgram {
+	pub fn new(path: Option<PathBuf>, fragment: &'static [u8], vertex: &'static [u8]) -> Self {
+		let watch = path.map(FileWatch::new);
+		Self {
+			program: load(watch.as_ref().map(|watch| &*watch.path), fragment, vertex),
+			watch,
+			vertex,
+		}
+	}
+
+	/// Recompiles the shader file if it changed. A broken edit keeps the program
+	/// that's already running, so a typo doesn't blank the buddy mid-session.
+	pub fn poll(&mut self, dt: f64) {
+		let Some(watch) = &mut self.watch else {
+			return;
+		};
+		if !watch.changed(dt) {
+			return;
+		}
+
+		match compile_file(&watch.path, self.vertex) {
+			Ok(program) => {
+				tracing::info!("reloaded {}", watch.path.display());
+				unsafe { gl::DeleteProgram(self.program) };
+				self.program = program;
+			}
+			Err(err) => tracing::error!("{err}"),
+		}
+	}
+
+	pub fn clean_up(&self) {
+		unsafe { gl::DeleteProgram(self.program) };
+	}
+}
+
+/// Checks a file's modification time every [`FileWatch::POLL_INTERVAL`] seconds.
+struct FileWatch {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+	timer: f64,
+}
+
+impl FileWatch {
+	const POLL_INTERVAL: f64 = 1.0;
+
+	fn new(path: PathBuf) -> Self {
+		Self {
+			modified: modified(&path),
+			path,
+			timer: Self::POLL_INTERVAL,
+		}
+	}
+
+	/// Whether the file changed, appeared or went away since it was last checked.
+	fn changed(&mut self, dt: f64) -> bool {
+		self.timer -= dt;
+		if self.timer > 0.0 {
+			return false;
+		}
+		self.timer = Self::POLL_INTERVAL;
+
+		let modified = modified(&self.path);
+		let changed = modified != self.modified;
+		self.modified = modified;
+		changed
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	std::fs::metadata(path)
+		.and_then(|metadata| metadata.modified())
+		.ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+
+	/// Writes `path` with a modification time of `second`, as quick edits can share one.
+	fn edit(path: &Path, second: u64) {
+		std::fs::write(path, "void main() {}").unwrap();
+		std::fs::File::options()
+			.write(true)
+			.open(path)
+			.unwrap()
+			.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(second))
+			.unwrap();
+	}
+
+	fn temp_shader(name: &str) -> PathBuf {
+		std::env::temp_dir().join(format!("funfriend-{name}-{}.frag", std::process::id()))
+	}
+
+	#[test]
+	fn checks_for_changes_once_per_poll_interval() {
+		let path = temp_shader("watched");
+		edit(&path, 1);
+		let mut watch = FileWatch::new(path.clone());
+		assert!(!watch.changed(FileWatch::POLL_INTERVAL));
+
+		edit(&path, 2);
+		assert!(!watch.changed(0.75));
+		assert!(watch.changed(0.25));
+		// Only reported once.
+		assert!(!watch.changed(FileWatch::POLL_INTERVAL));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn notices_files_appearing_and_going_away() {
+		let path = temp_shader("appearing");
+		let mut watch = FileWatch::new(path.clone());
+		assert!(!watch.changed(FileWatch::POLL_INTERVAL));
+
+		edit(&path, 1);
+		assert!(watch.changed(FileWatch::POLL_INTERVAL));
+		std::fs::remove_file(&path).unwrap();
+		assert!(watch.changed(FileWatch::POLL_INTERVAL));
+		assert!(!watch.changed(FileWatch::POLL_INTERVAL));
+	}
+}
@@ -0,0 +1,176 @@
+//! CPU rendering into an RGBA image, for machines without a GPU.
+//!
+//! Draws with the same [`geometry`] as the GL renderers, only sampling nearest
+//! texels with the wrap mode the GL textures use, so snapshots are close enough
+//! to what the windows show. Rows go top to bottom here, unlike GL.
+
+use image::{Rgba, RgbaImage};
+
+use super::{
+	super::{
+		animation,
+		buddy::BuddyDefinition,
+		font_manager::{markup::StyledText, BMSheet, Page, TextLayout},
+		vec2::Vec2,
+	},
+	geometry,
+};
+
+/// Loads a buddy's images once and draws any moment of its animation.
+pub struct BuddyImages {
+	pub frames: Vec<RgbaImage>,
+	/// Seconds each frame is shown for.
+	pub durations: Vec<f64>,
+	pub background: Option<RgbaImage>,
+}
+
+impl BuddyImages {
+	pub fn load(buddy: &dyn BuddyDefinition) -> Result<Self, String> {
+		let (frames, durations) = animation::load(&buddy.animation())?
+			.into_iter()
+			.map(|frame| (frame.image, frame.duration))
+			.unzip();
+		let background = buddy
+			.background_path()
+			.map(|path| open(&path))
+			.transpose()?;
+		Ok(Self {
+			frames,
+			durations,
+			background,
+		})
+	}
+
+	/// The buddy window `time` seconds in, with the body scaled to `size` inside a
+	/// `resolution` sized window.
+	pub fn render(&self, size: Vec2, resolution: Vec2, time: f64) -> RgbaImage {
+		let (width, height) = (resolution.x as u32, resolution.y as u32);
+		let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 0]));
+
+		let frame = &self.frames[animation::frame_at(&self.durations, time)];
+		let body_uv = geometry::body_uv_transform(
+			time as f32,
+			[size.x as f32, size.y as f32],
+			[resolution.x as f32, resolution.y as f32],
+		);
+
+		for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+			let (u, v) = geometry::window_uv(x, y, width, height);
+
+			// The background shader samples without flipping.
+			if let Some(background) = &self.background {
+				let color = sample(background, u, v, Wrap::Edge);
+				if color[3] >= 0.1 {
+					blend(pixel, color);
+				}
+			}
+
+			let (u, v) = body_uv.apply(u, v);
+			blend(pixel, sample(frame, u, v, Wrap::Edge));
+		}
+		canvas
+	}
+}
+
+/// A speech bubble's text on its black background, `time` seconds in so shaking
+/// and waving glyphs can be captured mid-motion.
+pub fn render_text(
+	text: &StyledText,
+	layout: &TextLayout,
+	sheet: &BMSheet,
+	size: Vec2,
+	time: f64,
+) -> Result<RgbaImage, String> {
+	let (width, height) = (size.x as i32, size.y as i32);
+	let mut canvas = RgbaImage::from_pixel(width as u32, height as u32, Rgba([0, 0, 0, 255]));
+
+	let pages: Vec<Vec<RgbaImage>> = sheet
+		.chain()
+		.map(|sheet| {
+			sheet
+				.pages
+				.iter()
+				.map(|page| match page {
+					Page::File(path) => open(path),
+					Page::Image(image) => Ok(image.clone()),
+				})
+				.collect()
+		})
+		.collect::<Result<_, String>>()?;
+
+	let quads = geometry::glyph_quads(layout, sheet, text, width, height);
+	for (i, quad) in quads.iter().enumerate() {
+		let Some(page) = pages[quad.sheet].get(quad.page) else {
+			continue;
+		};
+		let (x, y) = quad.position(i, time as f32);
+		let (glyph_w, glyph_h) = (quad.width as i32, quad.height as i32);
+		let (u, v, uv_w, uv_h) = quad.uv;
+
+		// Quad y points up and is the glyph's bottom edge.
+		let left = x.round() as i32;
+		let top = height - (y.round() as i32 + glyph_h);
+		for gy in 0..glyph_h {
+			for gx in 0..glyph_w {
+				let (px, py) = (left + gx, top + gy);
+				if px < 0 || py < 0 || px >= width || py >= height {
+					continue;
+				}
+
+				let texel = sample(
+					page,
+					u + (gx as f32 + 0.5) / quad.width * uv_w,
+					v + (gy as f32 + 0.5) / quad.height * uv_h,
+					Wrap::Border,
+				);
+				let mut color = [0.0; 4];
+				for c in 0..4 {
+					color[c] = texel[c] * quad.color[c];
+				}
+				if color[3] >= 0.1 {
+					blend(canvas.get_pixel_mut(px as u32, py as u32), color);
+				}
+			}
+		}
+	}
+	Ok(canvas)
+}
+
+fn open(path: &str) -> Result<RgbaImage, String> {
+	image::open(path)
+		.map(|image| image.to_rgba8())
+		.map_err(|e| format!("failed to open {path}: {e}"))
+}
+
+/// What sampling outside a texture gives, as set with `GL_TEXTURE_WRAP_S/T`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Wrap {
+	/// `GL_CLAMP_TO_EDGE`, the default for textures in `texture.rs`.
+	Edge,
+	/// `GL_CLAMP_TO_BORDER` with the default transparent black border, for font pages.
+	Border,
+}
+
+/// Nearest texel at texture coordinates `(u, v)`, with `v = 0` being the first row
+/// like a texture uploaded from an image.
+fn sample(image: &RgbaImage, u: f32, v: f32, wrap: Wrap) -> [f32; 4] {
+	let x = (u * image.width() as f32).floor() as i64;
+	let y = (v * image.height() as f32).floor() as i64;
+	let (width, height) = (image.width() as i64, image.height() as i64);
+	if wrap == Wrap::Border && (x < 0 || y < 0 || x >= width || y >= height) {
+		return [0.0; 4];
+	}
+	image
+		.get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32)
+		.0
+		.map(|c| c as f32 / 255.0)
+}
+
+/// `glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA)` on every channel.
+fn blend(pixel: &mut Rgba<u8>, color: [f32; 4]) {
+	let alpha = color[3];
+	for (dst, src) in pixel.0.iter_mut().zip(color) {
+		let dst_f = *dst as f32 / 255.0;
+		*dst = ((src * alpha + dst_f * (1.0 - alpha)) * 255.0).round() as u8;
+	}
+}
@@ -1,18 +1,27 @@
-use glfw::{Action, Context, Key, WindowEvent};
+use clap::Parser as _;
+use glfw::{fail_on_errors, Action, Key, WindowEvent};
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 
+mod animation;
+mod audio;
 mod buddy;
+mod cli;
 mod config;
+#[cfg(unix)]
+mod control;
 mod ease;
 mod font_manager;
 mod glfn;
 mod graphics;
 mod logger;
+mod record;
+mod snapshot;
 mod texture;
 mod vec2;
 mod window;
 
+use audio::Audio;
 use buddy::BuddyDefinition;
 use vec2::Vec2;
 use window::{Window, Windowed};
@@ -25,21 +34,51 @@ pub const NOP_FRAG: &[u8] = include_bytes!("glsl/nop.frag");
 pub const NOP_VERT: &[u8] = include_bytes!("glsl/nop.vert");
 pub const BASIC_FRAG: &[u8] = include_bytes!("glsl/basic_fragment.frag");
 pub const BASIC_VERT: &[u8] = include_bytes!("glsl/basic_vertex.vert");
+pub const TEXT_FRAG: &[u8] = include_bytes!("glsl/text.frag");
+pub const TEXT_VERT: &[u8] = include_bytes!("glsl/text.vert");
 
 pub struct App {
+	glfw: glfw::Glfw,
 	contexts: Vec<Rc<RefCell<dyn Windowed>>>,
+	/// Buddy contexts in spawn order, so the control socket can address them by index.
+	buddies: Vec<Weak<RefCell<buddy::Context>>>,
 	buddy: Rc<RefCell<dyn BuddyDefinition>>,
+	audio: Rc<RefCell<Audio>>,
 	config: config::Config,
+	/// Command-line overrides, reapplied on top of the file whenever it's reloaded.
+	overrides: cli::Overrides,
+	config_watcher: config::Watcher,
+	#[cfg(unix)]
+	control: Option<control::ControlServer>,
 }
 
 impl App {
-	fn new(config: config::Config) -> Self {
-		let buddy = buddy::make_buddy(config.buddy.r#type);
+	fn new(config: config::Config, overrides: cli::Overrides) -> Self {
+		let mut glfw = glfw::init(glfw::fail_on_errors!()).unwrap();
+		let buddy = buddy::make_buddy(&config.buddy.r#type);
+		let audio = Rc::new(RefCell::new(Audio::new(&config.sound)));
+		let context = buddy::make_context(&config, buddy.clone(), audio.clone(), &mut glfw);
+
+		#[cfg(unix)]
+		let control = match control::ControlServer::bind() {
+			Ok(control) => Some(control),
+			Err(err) => {
+				tracing::warn!("control socket disabled: {err}");
+				None
+			}
+		};
 
 		Self {
-			contexts: vec![buddy::make_context(&config, buddy.clone())],
+			glfw,
+			buddies: vec![Rc::downgrade(&context)],
+			contexts: vec![context],
 			buddy,
+			audio,
 			config,
+			overrides,
+			config_watcher: config::Watcher::new(),
+			#[cfg(unix)]
+			control,
 		}
 	}
 
@@ -104,52 +143,38 @@ impl App {
 	// }
 
 	fn run(&mut self) {
-		let config = config::read();
-
-		let mut last_t = 0.0;
+		let mut last_t = self.glfw.get_time();
 		while !self.contexts.is_empty() {
-			self.contexts.retain_mut(|context| {
-				let mut context = context.borrow_mut();
-				// tracing::info!("new frame");
-				context.get_window().glfw.poll_events();
-				let dt = context.get_window().glfw.get_time() - last_t;
-				last_t = context.get_window().glfw.get_time();
-				let flushed_events = glfw::flush_messages(&context.get_window().events);
-				let mut should_close = false;
-				let mut was_clicked = false;
-				let mut was_released = false;
-				for (_, event) in flushed_events {
-					match event {
-						WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-							tracing::warn!("should close");
-							should_close = true;
-						}
-						WindowEvent::MouseButton(_, Action::Press, _) => {
-							tracing::warn!("was clicked");
-							was_clicked = true;
-						}
-						WindowEvent::MouseButton(_, Action::Release, _) => {
-							tracing::warn!("was released");
-							was_released = true;
-						}
-						_ => (),
-					}
-				}
-				if was_clicked {
-					let cursor_pos = context.get_window().handle.get_cursor_pos();
-					let cursor_pos = Vec2::new(cursor_pos.0, cursor_pos.1);
+			let mut events = Vec::new();
+			self.glfw
+				.wait_events_timeout_unbuffered(1.0 / 120.0, |window_id, (_, event)| {
+					events.push((window_id, event));
+					None
+				});
 
-					context.on_click(cursor_pos);
-				}
-				if was_released {
-					let cursor_pos = context.get_window().handle.get_cursor_pos();
-					let cursor_pos = Vec2::new(cursor_pos.0, cursor_pos.1);
+			let t = self.glfw.get_time();
+			let dt = t - last_t;
+			last_t = t;
 
-					context.on_release(cursor_pos);
-				}
-				if should_close {
-					context.get_window().handle.set_should_close(true);
+			for (window_id, event) in events {
+				let context = self
+					.contexts
+					.iter()
+					.find(|context| context.borrow_mut().get_window().id() == window_id);
+				if let Some(context) = context {
+					Self::handle_event(&mut *context.borrow_mut(), event);
 				}
+			}
+
+			#[cfg(unix)]
+			self.poll_control();
+
+			if let Some(config) = self.config_watcher.poll(dt) {
+				self.reload_config(config);
+			}
+
+			self.contexts.retain_mut(|context| {
+				let mut context = context.borrow_mut();
 				if context.should_close() {
 					tracing::info!("trying to close?");
 					context.clean_up();
@@ -157,23 +182,239 @@ impl App {
 				} else {
 					// tracing::info!("running update");
 					context.update(dt);
-					let window = context.get_window();
-					window.handle.swap_buffers();
-					window.glfw.wait_events_timeout(1.0 / 120.0);
 					true
 				}
 			});
 		}
+	}
+
+	fn reload_config(&mut self, config: config::Config) {
+		let Some(config) = self.overrides.reload(config, &self.config) else {
+			return;
+		};
+		tracing::info!("config file changed, reloading");
+		let old = std::mem::replace(&mut self.config, config);
+		let config = &self.config;
+
+		if old.sound.output != config.sound.output {
+			*self.audio.borrow_mut() = Audio::new(&config.sound);
+		} else if old.sound.master_volume != config.sound.master_volume {
+			self.audio
+				.borrow_mut()
+				.set_master_volume(config.sound.master_volume);
+		}
+
+		// Only buddies showing the configured buddy follow a type change, ones
+		// spawned with an explicit type keep theirs.
+		let new_buddy = (old.buddy.r#type != config.buddy.r#type)
+			.then(|| buddy::make_buddy(&config.buddy.r#type));
+		let resized = old.window.size != config.window.size;
+		let shaders_changed = old.buddy.shader != config.buddy.shader
+			|| old.buddy.background_shader != config.buddy.background_shader;
+
+		for context in self.buddies.iter().filter_map(Weak::upgrade) {
+			let mut context = context.borrow_mut();
+			match &new_buddy {
+				Some(buddy) if Rc::ptr_eq(&context.buddy, &self.buddy) => {
+					context.set_buddy(config, buddy.clone())
+				}
+				_ if resized || shaders_changed => context.rebuild_renderer(config),
+				_ => {}
+			}
+			if old.buddy.behavior != config.buddy.behavior {
+				context.set_behavior(config.buddy.behavior);
+			}
+			context.speed = config.buddy.speed;
+			context.fling_friction = config.buddy.fling_friction;
+			context.fling_restitution = config.buddy.fling_restitution;
+			context.sleep_after = config.buddy.sleep_after;
+			context.dialog_settings = config.dialog.clone();
+		}
 
-		config::write(&config);
+		if let Some(buddy) = new_buddy {
+			self.buddy = buddy;
+		}
+	}
+
+	#[cfg(unix)]
+	fn poll_control(&mut self) {
+		// Taken out for the duration of the poll so requests can borrow the whole app.
+		let Some(mut control) = self.control.take() else {
+			return;
+		};
+		control.poll(|request| self.handle_request(request));
+		self.control = Some(control);
+	}
+
+	#[cfg(unix)]
+	fn handle_request(&mut self, request: control::Request) -> control::Response {
+		use control::{Command, Response};
+
+		if let Command::SpawnBuddy { r#type } = request.command {
+			let buddy = match r#type {
+				Some(r#type) => buddy::make_buddy(&r#type),
+				None => self.buddy.clone(),
+			};
+			let context =
+				buddy::make_context(&self.config, buddy, self.audio.clone(), &mut self.glfw);
+			self.buddies.push(Rc::downgrade(&context));
+			self.contexts.push(context);
+			return Response {
+				buddy: Some(self.buddies.len() - 1),
+				..Response::ok()
+			};
+		}
+
+		if let Command::Quit = request.command {
+			for context in &self.contexts {
+				context
+					.borrow_mut()
+					.get_window()
+					.handle
+					.set_should_close(true);
+			}
+			return Response::ok();
+		}
+
+		let Some(context) = self.buddies.get(request.buddy).and_then(Weak::upgrade) else {
+			return Response::error(format!("no buddy with index {}", request.buddy));
+		};
+		let mut context = context.borrow_mut();
+		match request.command {
+			Command::Say { text } => context.say(text),
+			Command::Goto { x, y, duration } => context.goto(Vec2::new(x, y), duration, true),
+			Command::SetBehavior { behavior } => context.set_behavior(behavior),
+			Command::SpawnBuddy { .. } | Command::Quit => unreachable!(),
+		}
+		Response::ok()
+	}
+
+	fn handle_event(context: &mut dyn Windowed, event: WindowEvent) {
+		match event {
+			WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
+				tracing::warn!("should close");
+				context.get_window().handle.set_should_close(true);
+			}
+			WindowEvent::MouseButton(_, Action::Press, _) => {
+				tracing::warn!("was clicked");
+				let cursor_pos = context.get_window().handle.get_cursor_pos();
+				context.on_click(Vec2::new(cursor_pos.0, cursor_pos.1));
+			}
+			WindowEvent::MouseButton(_, Action::Release, _) => {
+				tracing::warn!("was released");
+				let cursor_pos = context.get_window().handle.get_cursor_pos();
+				context.on_release(Vec2::new(cursor_pos.0, cursor_pos.1));
+			}
+			_ => (),
+		}
 	}
 }
 
 fn main() {
-	logger::init();
+	let cli = cli::Cli::parse();
+
+	match cli.command.unwrap_or(cli::Command::Run) {
+		cli::Command::Run => {
+			logger::init();
+
+			let mut config = config::load();
+			cli.overrides.apply(&mut config);
+			if let Err(errors) = config.validate() {
+				for error in errors {
+					eprintln!("{error}");
+				}
+				std::process::exit(1);
+			}
 
-	let config = config::read();
+			let mut app = App::new(config, cli.overrides);
+			app.run();
+		}
+		cli::Command::ConfigPath => println!("{}", config::get_config_path().display()),
+		cli::Command::CheckConfig => match config::read() {
+			Ok(config::Loaded {
+				migrated_from: Some(version),
+				..
+			}) => println!("ok, will be migrated from version {version}"),
+			Ok(_) => println!("ok"),
+			Err(err) => {
+				eprintln!("{err}");
+				std::process::exit(1);
+			}
+		},
+		cli::Command::Record {
+			output,
+			duration,
+			fps,
+		} => {
+			let mut config = config::load();
+			cli.overrides.apply(&mut config);
+			if let Err(err) = record::run(&config, &output, duration, fps) {
+				eprintln!("{err}");
+				std::process::exit(1);
+			}
+		}
+		cli::Command::Snapshot { dir, check, actual } => {
+			if let Err(err) = snapshot::run(&dir, check, &actual) {
+				eprintln!("{err}");
+				std::process::exit(1);
+			}
+		}
+		cli::Command::DefaultConfig => println!(
+			"{}",
+			serde_json::to_string_pretty(&config::Config::default())
+				.expect("failed to serialize config")
+		),
+		command => {
+			// A running instance keeps its own settings, use `set-behavior` or `spawn` instead.
+			let given = cli.overrides.given();
+			if !given.is_empty() {
+				eprintln!(
+					"{} can only be used when starting a buddy, not with commands for a running one",
+					given.join(", ")
+				);
+				std::process::exit(2);
+			}
+			if let Err(err) = send_command(command) {
+				eprintln!("{err}");
+				std::process::exit(1);
+			}
+		}
+	}
+}
+
+#[cfg(unix)]
+fn send_command(command: cli::Command) -> Result<(), String> {
+	let (buddy, command) = match command {
+		cli::Command::Say { text, to } => (to, control::Command::Say { text }),
+		cli::Command::Goto { x, y, duration, to } => {
+			(to, control::Command::Goto { x, y, duration })
+		}
+		cli::Command::SetBehavior { behavior, to } => {
+			(to, control::Command::SetBehavior { behavior })
+		}
+		cli::Command::Spawn { r#type } => (0, control::Command::SpawnBuddy { r#type }),
+		cli::Command::Quit => (0, control::Command::Quit),
+		cli::Command::Run
+		| cli::Command::ConfigPath
+		| cli::Command::CheckConfig
+		| cli::Command::DefaultConfig
+		| cli::Command::Record { .. }
+		| cli::Command::Snapshot { .. } => {
+			unreachable!()
+		}
+	};
+
+	let response = control::send(&control::Request { buddy, command })?;
+	if let Some(buddy) = response.buddy {
+		println!("{buddy}");
+	}
+	match response.error {
+		Some(err) if !response.ok => Err(err),
+		_ => Ok(()),
+	}
+}
 
-	let mut app = App::new(config);
-	app.run();
+#[cfg(not(unix))]
+fn send_command(_: cli::Command) -> Result<(), String> {
+	Err("talking to a running instance is only supported on Unix".to_string())
 }
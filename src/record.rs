@@ -0,0 +1,251 @@
+//! Renders a buddy's animation to an animated image file with the software
+//! renderer, for README gifs, emotes and docs.
+
+use std::{
+	fs::File,
+	io::{BufWriter, Write},
+	path::Path,
+};
+
+use image::{
+	codecs::{gif::GifEncoder, webp::WebPEncoder},
+	Delay, ExtendedColorType, Frame, RgbaImage,
+};
+
+use super::{
+	buddy::{self, Context},
+	config,
+	graphics::software::BuddyImages,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Gif,
+	Apng,
+	WebP,
+}
+
+impl Format {
+	/// Picks the format from the file extension, `.png` meaning APNG.
+	pub fn from_path(path: &Path) -> Result<Self, String> {
+		let extension = path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.map(str::to_lowercase);
+		match extension.as_deref() {
+			Some("gif") => Ok(Self::Gif),
+			Some("png" | "apng") => Ok(Self::Apng),
+			Some("webp") => Ok(Self::WebP),
+			_ => Err(format!(
+				"don't know how to record to {}, use a .gif, .png, .apng or .webp file",
+				path.display()
+			)),
+		}
+	}
+}
+
+/// Records `duration` seconds of the configured buddy at `fps` frames per second.
+pub fn run(config: &config::Config, path: &Path, duration: f64, fps: f64) -> Result<(), String> {
+	let format = Format::from_path(path)?;
+	if !(duration > 0.0 && fps > 0.0) {
+		return Err("duration and fps have to be positive".to_string());
+	}
+
+	let buddy = buddy::make_buddy(&config.buddy.r#type);
+	let images = BuddyImages::load(&*buddy.borrow())?;
+	let size = config.window.size;
+	let resolution = Context::window_size_for(size);
+
+	let count = (duration * fps).round().max(1.0) as usize;
+	let frames: Vec<RgbaImage> = (0..count)
+		.map(|i| images.render(size, resolution, i as f64 / fps))
+		.collect();
+	let delay_ms = (1000.0 / fps).round() as u32;
+
+	let file =
+		File::create(path).map_err(|e| format!("failed to create {}: {e}", path.display()))?;
+	let writer = BufWriter::new(file);
+	let result = match format {
+		Format::Gif => write_gif(writer, frames, delay_ms),
+		Format::Apng => write_apng(writer, &frames, delay_ms),
+		Format::WebP => write_webp(writer, &frames, delay_ms),
+	};
+	result.map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+fn write_gif(writer: impl Write, frames: Vec<RgbaImage>, delay_ms: u32) -> Result<(), String> {
+	let mut encoder = GifEncoder::new(writer);
+	encoder
+		.set_repeat(image::codecs::gif::Repeat::Infinite)
+		.map_err(|e| e.to_string())?;
+	let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+	encoder
+		.encode_frames(
+			frames
+				.into_iter()
+				.map(|frame| Frame::from_parts(frame, 0, 0, delay)),
+		)
+		.map_err(|e| e.to_string())
+}
+
+fn write_apng(writer: impl Write, frames: &[RgbaImage], delay_ms: u32) -> Result<(), String> {
+	let (width, height) = frames[0].dimensions();
+	let mut encoder = png::Encoder::new(writer, width, height);
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	encoder
+		.set_animated(frames.len() as u32, 0)
+		.map_err(|e| e.to_string())?;
+	encoder
+		.set_frame_delay(delay_ms.min(u16::MAX as u32) as u16, 1000)
+		.map_err(|e| e.to_string())?;
+	// Every frame is drawn in full, so none of them should show through the next.
+	encoder
+		.set_dispose_op(png::DisposeOp::Background)
+		.map_err(|e| e.to_string())?;
+	encoder
+		.set_blend_op(png::BlendOp::Source)
+		.map_err(|e| e.to_string())?;
+
+	let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+	for frame in frames {
+		writer
+			.write_image_data(frame.as_raw())
+			.map_err(|e| e.to_string())?;
+	}
+	writer.finish().map_err(|e| e.to_string())
+}
+
+/// The `image` crate only writes still WebP images, so every frame is encoded on
+/// its own and the resulting `VP8L` chunks are wrapped in an animated container.
+fn write_webp(mut writer: impl Write, frames: &[RgbaImage], delay_ms: u32) -> Result<(), String> {
+	let (width, height) = frames[0].dimensions();
+
+	let mut vp8x = vec![0x10 | 0x02, 0, 0, 0]; // alpha and animation flags
+	vp8x.extend_from_slice(&u24(width - 1));
+	vp8x.extend_from_slice(&u24(height - 1));
+
+	let mut anim = vec![0, 0, 0, 0]; // transparent background
+	anim.extend_from_slice(&0u16.to_le_bytes()); // loop forever
+
+	let mut body = b"WEBP".to_vec();
+	write_chunk(&mut body, b"VP8X", &vp8x);
+	write_chunk(&mut body, b"ANIM", &anim);
+	for frame in frames {
+		let mut still = Vec::new();
+		WebPEncoder::new_lossless(&mut still)
+			.encode(frame.as_raw(), width, height, ExtendedColorType::Rgba8)
+			.map_err(|e| e.to_string())?;
+		let bitstream = find_chunk(&still, b"VP8L").ok_or("encoded frame has no VP8L chunk")?;
+
+		let mut anmf = Vec::new();
+		anmf.extend_from_slice(&u24(0)); // x
+		anmf.extend_from_slice(&u24(0)); // y
+		anmf.extend_from_slice(&u24(width - 1));
+		anmf.extend_from_slice(&u24(height - 1));
+		anmf.extend_from_slice(&u24(delay_ms));
+		anmf.push(0b10 | 0b01); // don't blend, dispose to background
+		write_chunk(&mut anmf, b"VP8L", bitstream);
+		write_chunk(&mut body, b"ANMF", &anmf);
+	}
+
+	writer.write_all(b"RIFF").map_err(|e| e.to_string())?;
+	writer
+		.write_all(&(body.len() as u32).to_le_bytes())
+		.map_err(|e| e.to_string())?;
+	writer.write_all(&body).map_err(|e| e.to_string())?;
+	writer.flush().map_err(|e| e.to_string())
+}
+
+fn u24(value: u32) -> [u8; 3] {
+	let bytes = value.to_le_bytes();
+	[bytes[0], bytes[1], bytes[2]]
+}
+
+/// Appends a RIFF chunk, padded to an even length.
+fn write_chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+	out.extend_from_slice(name);
+	out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	out.extend_from_slice(data);
+	if data.len() % 2 == 1 {
+		out.push(0);
+	}
+}
+
+/// Payload of the first `name` chunk in a RIFF WebP file.
+fn find_chunk<'a>(riff: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+	let mut rest = riff.get(12..)?;
+	while rest.len() >= 8 {
+		let size = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+		let data = rest.get(8..8 + size)?;
+		if &rest[..4] == name {
+			return Some(data);
+		}
+		rest = rest.get(8 + size + size % 2..)?;
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use image::{
+		codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+		AnimationDecoder, Frames, Rgba,
+	};
+
+	use super::*;
+
+	fn frames() -> Vec<RgbaImage> {
+		[[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+			.into_iter()
+			.map(|color| RgbaImage::from_pixel(6, 5, Rgba(color)))
+			.collect()
+	}
+
+	fn check(decoded: Frames, delay_ms: u32) {
+		let decoded = decoded.collect_frames().unwrap();
+		assert_eq!(decoded.len(), 3);
+		for (frame, expected) in decoded.iter().zip(frames()) {
+			let (numer, denom) = frame.delay().numer_denom_ms();
+			assert_eq!(numer / denom, delay_ms);
+			assert_eq!(frame.buffer().dimensions(), (6, 5));
+			assert_eq!(frame.buffer().get_pixel(2, 2), expected.get_pixel(2, 2));
+		}
+	}
+
+	#[test]
+	fn gif_recordings_decode_back() {
+		let mut out = Vec::new();
+		write_gif(&mut out, frames(), 40).unwrap();
+		check(GifDecoder::new(Cursor::new(out)).unwrap().into_frames(), 40);
+	}
+
+	#[test]
+	fn apng_recordings_decode_back() {
+		let mut out = Vec::new();
+		write_apng(&mut out, &frames(), 40).unwrap();
+		let decoder = PngDecoder::new(Cursor::new(out)).unwrap();
+		assert!(decoder.is_apng().unwrap());
+		check(decoder.apng().unwrap().into_frames(), 40);
+	}
+
+	#[test]
+	fn webp_recordings_decode_back() {
+		let mut out = Vec::new();
+		write_webp(&mut out, &frames(), 40).unwrap();
+		check(
+			WebPDecoder::new(Cursor::new(out)).unwrap().into_frames(),
+			40,
+		);
+	}
+
+	#[test]
+	fn picks_the_format_from_the_extension() {
+		assert_eq!(Format::from_path(Path::new("a.GIF")), Ok(Format::Gif));
+		assert_eq!(Format::from_path(Path::new("a.png")), Ok(Format::Apng));
+		assert_eq!(Format::from_path(Path::new("a.webp")), Ok(Format::WebP));
+		assert!(Format::from_path(Path::new("a.mp4")).is_err());
+	}
+}
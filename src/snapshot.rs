@@ -0,0 +1,148 @@
+//! Golden image snapshots of the software renderer, so rendering changes can be
+//! caught on machines without a GPU.
+//!
+//! `funfriend-rust snapshot tests/golden` writes one PNG per snapshot, and
+//! `funfriend-rust snapshot --check tests/golden` compares against them instead,
+//! writing `<name>.actual.png` for any that don't match into a separate directory
+//! so the golden one stays clean.
+
+use std::path::Path;
+
+use image::RgbaImage;
+
+use super::{
+	buddy::{self, BuddyDefinition},
+	config::{self, BuddyType},
+	font_manager::FontMan,
+	graphics::{dialog::Dialog, software},
+};
+
+/// Seconds into the animation every snapshot is taken at, chosen so the bounce is
+/// visibly off center.
+const TIME: f64 = 0.5;
+/// How far apart two channels can be before a pixel counts as different.
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Fraction of differing pixels a snapshot can have and still match, for float
+/// differences between platforms landing on a neighbouring texel.
+const PIXEL_TOLERANCE: f64 = 0.01;
+const SAMPLE_DIALOG: &str = "HELLO [color=#f00]INTERLOPER[/color]! [wave]HOW ARE YOU[/wave]?";
+
+fn snapshots() -> Vec<(String, Result<RgbaImage, String>)> {
+	let buddy_types = [
+		BuddyType::Funfriend,
+		BuddyType::Catfriend,
+		BuddyType::God,
+		BuddyType::Pack("bstrd".to_string()),
+	];
+
+	let size = config::Config::default().window.size;
+	let mut snapshots: Vec<_> = buddy_types
+		.iter()
+		.map(|r#type| {
+			let buddy = buddy::make_buddy(r#type);
+			let buddy = buddy.borrow();
+			let image = software::BuddyImages::load(&*buddy)
+				.map(|images| images.render(size, buddy::Context::window_size_for(size), TIME));
+			(buddy.name().to_lowercase(), image)
+		})
+		.collect();
+
+	let buddy = buddy::make_buddy(&BuddyType::Funfriend);
+	snapshots.push(("dialog".to_string(), render_dialog(&*buddy.borrow())));
+	snapshots
+}
+
+fn render_dialog(buddy: &dyn BuddyDefinition) -> Result<RgbaImage, String> {
+	let sheet =
+		FontMan::load_with_fallbacks(buddy.font(), buddy.font_size(), &buddy.fallback_fonts())?;
+	let (text, layout, size) = Dialog::layout(SAMPLE_DIALOG, &sheet);
+	software::render_text(&text, &layout, &sheet, size, TIME)
+}
+
+/// Writes every snapshot into `dir`, or with `check` compares them to the ones
+/// already there and writes those that differ into `actual`.
+pub fn run(dir: &Path, check: bool, actual: &Path) -> Result<(), String> {
+	if !check {
+		std::fs::create_dir_all(dir)
+			.map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+	}
+
+	let mut failures = Vec::new();
+	for (name, image) in snapshots() {
+		let path = dir.join(format!("{name}.png"));
+		let result = image.and_then(|image| {
+			if !check {
+				return save(&image, &path);
+			}
+			let result = compare(&path, &image);
+			if result.is_err() {
+				let _ = std::fs::create_dir_all(actual);
+				let _ = save(&image, &actual.join(format!("{name}.actual.png")));
+			}
+			result
+		});
+		match result {
+			Ok(()) => println!("{name}: ok"),
+			Err(err) => {
+				println!("{name}: {err}");
+				failures.push(name);
+			}
+		}
+	}
+
+	if failures.is_empty() {
+		Ok(())
+	} else {
+		Err(format!("snapshots failed: {}", failures.join(", ")))
+	}
+}
+
+fn compare(golden: &Path, actual: &RgbaImage) -> Result<(), String> {
+	let golden = image::open(golden)
+		.map_err(|e| format!("failed to open {}: {e}", golden.display()))?
+		.to_rgba8();
+	if golden.dimensions() != actual.dimensions() {
+		return Err(format!(
+			"size changed from {:?} to {:?}",
+			golden.dimensions(),
+			actual.dimensions()
+		));
+	}
+
+	let differing = golden
+		.pixels()
+		.zip(actual.pixels())
+		.filter(|(a, b)| {
+			a.0.iter()
+				.zip(b.0)
+				.any(|(a, b)| a.abs_diff(b) > CHANNEL_TOLERANCE)
+		})
+		.count();
+	let total = (golden.width() * golden.height()) as f64;
+	if differing as f64 > total * PIXEL_TOLERANCE {
+		return Err(format!("{differing} of {total} pixels differ"));
+	}
+	Ok(())
+}
+
+fn save(image: &RgbaImage, path: &Path) -> Result<(), String> {
+	image
+		.save(path)
+		.map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn golden_images_match() {
+		let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+		run(
+			&root.join("tests/golden"),
+			true,
+			&root.join("target/snapshots"),
+		)
+		.unwrap();
+	}
+}
@@ -1,4 +1,6 @@
 use gl::types::*;
+
+use super::animation;
 // use glium::Display;
 // use glium::texture::{Texture2d, RawImage2d, SrgbTexture2d};
 
@@ -12,7 +14,8 @@ pub struct SizedTexture {
 #[derive(Debug)]
 pub struct TextureBasket {
 	pub textures: Vec<SizedTexture>,
-	pub fps: f64,
+	/// Seconds each texture is shown for.
+	pub durations: Vec<f64>,
 	pub t: f64,
 }
 
@@ -24,17 +27,34 @@ const DEFAULT_TEXTURE_PARAMS: [(GLuint, GLuint); 4] = [
 ];
 
 impl TextureBasket {
-	pub fn new(textures: Vec<SizedTexture>, fps: f64) -> TextureBasket {
+	pub fn new(textures: Vec<SizedTexture>, durations: Vec<f64>) -> TextureBasket {
 		Self {
 			textures,
-			fps,
+			durations,
 			t: 0.0,
 		}
 	}
 
+	/// Uploads every frame of `source`.
+	pub fn load(source: &animation::Source) -> Result<TextureBasket, String> {
+		let frames = animation::load(source)?;
+		let textures = frames
+			.iter()
+			.map(|frame| upload_texture(&frame.image, None))
+			.collect();
+		Ok(Self::new(
+			textures,
+			frames.iter().map(|frame| frame.duration).collect(),
+		))
+	}
+
 	pub fn frame(&self) -> usize {
-		let frame = (self.t * self.fps).floor() as usize;
-		frame % self.textures.len()
+		animation::frame_at(&self.durations, self.t) % self.textures.len()
+	}
+
+	/// Whether the animation has played through at least once.
+	pub fn finished(&self) -> bool {
+		self.t >= self.durations.iter().sum()
 	}
 
 	pub fn texture(&self) -> &SizedTexture {
@@ -44,6 +64,11 @@ impl TextureBasket {
 	pub fn update(&mut self, delta: f64) {
 		self.t += delta;
 	}
+
+	pub fn clean_up(&self) {
+		let textures: Vec<GLuint> = self.textures.iter().map(|texture| texture.tex).collect();
+		unsafe { gl::DeleteTextures(textures.len() as GLsizei, textures.as_ptr()) };
+	}
 }
 
 pub fn load_texture(
@@ -52,6 +77,13 @@ pub fn load_texture(
 ) -> Result<SizedTexture, String> {
 	let img = image::open(filepath).map_err(|e| format!("Failed to open image: {}", e));
 	let img = img?.to_rgba8();
+	Ok(upload_texture(&img, params))
+}
+
+pub fn upload_texture(
+	img: &image::RgbaImage,
+	params: Option<[(GLuint, GLuint); 4]>,
+) -> SizedTexture {
 	let (width, height) = img.dimensions();
 	let params = params.unwrap_or(DEFAULT_TEXTURE_PARAMS);
 
@@ -79,9 +111,9 @@ pub fn load_texture(
 		gl::GenerateMipmap(gl::TEXTURE_2D);
 	}
 
-	Ok(SizedTexture {
+	SizedTexture {
 		tex: texture,
 		width,
 		height,
-	})
+	}
 }
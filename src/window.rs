@@ -1,5 +1,3 @@
-use glfw::fail_on_errors;
-
 use super::vec2::Vec2;
 
 pub trait Windowed {
@@ -12,15 +10,11 @@ pub trait Windowed {
 }
 
 pub struct Window {
-	pub(crate) glfw: glfw::Glfw,
 	pub(crate) handle: glfw::PWindow,
-	pub(crate) events: glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
 }
 
 impl Window {
-	pub fn new(width: u32, height: u32, title: &str) -> Self {
-		let mut glfw = glfw::init(glfw::fail_on_errors!()).unwrap();
-
+	pub fn new(glfw: &mut glfw::Glfw, width: u32, height: u32, title: &str) -> Self {
 		glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
 		glfw.window_hint(glfw::WindowHint::Decorated(false));
 		glfw.window_hint(glfw::WindowHint::Resizable(false));
@@ -31,7 +25,9 @@ impl Window {
 		glfw.window_hint(glfw::WindowHint::AlphaBits(Some(8)));
 		// glfw.window_hint(glfw::WindowHint::Visible(false));
 
-		let (mut window, events) = glfw
+		// Events are routed by window id from the app's event loop, so the
+		// per-window receiver is never read.
+		let (mut window, _) = glfw
 			.create_window(width, height, title, glfw::WindowMode::Windowed)
 			.expect("failed to create GLFW window");
 
@@ -41,10 +37,18 @@ impl Window {
 		window.set_cursor_pos_polling(true);
 		window.set_mouse_button_polling(true);
 
-		Self {
-			glfw,
-			handle: window,
-			events,
-		}
+		Self { handle: window }
+	}
+
+	pub fn id(&self) -> glfw::WindowId {
+		use glfw::Context as _;
+		self.handle.window_id()
+	}
+
+	/// Makes this window's GL context current and loads GL function pointers for it.
+	pub fn make_current(&mut self) {
+		use glfw::Context as _;
+		self.handle.make_current();
+		gl::load_with(|s| self.handle.get_proc_address(s) as *const _);
 	}
 }